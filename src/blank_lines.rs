@@ -0,0 +1,119 @@
+use crate::{config::Config, tokens::Token};
+
+fn depth_change(line: &[Token]) -> isize {
+    line.iter()
+        .map(|e| match e {
+            Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen => 1,
+            Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn is_word(token: Option<&Token>, words: &[&str]) -> bool {
+    matches!(token, Some(Token::Word(w)) if words.contains(&w.as_str()))
+}
+
+// `def`, `export def`, `module`, `extern` and friends, or a region that isn't formatted holding
+// one
+fn is_definition(line: &[Token], config: &Config) -> bool {
+    const DEFINITIONS: &[&str] = &["def", "def-env", "module", "extern"];
+
+    if let Some(Token::Verbatim(text)) = line.first() {
+        let tokens = crate::tokenize(text, config);
+        return tokens
+            .split(|e| e == &Token::NewLine)
+            .find(|e| !e.is_empty() && !is_attached(e))
+            .is_some_and(|e| is_definition(e, config));
+    }
+    match is_word(line.first(), &["export"]) {
+        true => is_word(line.get(1), DEFINITIONS),
+        false => is_word(line.first(), DEFINITIONS),
+    }
+}
+
+// comments and attributes directly above a definition belong to it
+fn is_attached(line: &[Token]) -> bool {
+    matches!(
+        line.first(),
+        Some(Token::CommentBlock(_)) | Some(Token::AttSomething(_))
+    )
+}
+
+// Caps runs of blank lines, removes them at the start and end of blocks and puts exactly one
+// blank line between two top level definitions
+pub fn normalise_blank_lines(tokens: Vec<Token>, config: &Config) -> Vec<Token> {
+    let lines = tokens
+        .split(|e| e == &Token::NewLine)
+        .map(|e| e.to_vec())
+        .collect::<Vec<_>>();
+
+    let mut depths = Vec::with_capacity(lines.len());
+    let mut depth = 0;
+    for line in &lines {
+        depths.push(depth);
+        depth = (depth + depth_change(line)).max(0);
+    }
+
+    // first line of each top level definition including what is attached to it, and the line
+    // it ends on
+    let mut item_start = vec![false; lines.len()];
+    let mut item_end = vec![false; lines.len()];
+    for (index, line) in lines.iter().enumerate() {
+        if depths[index] != 0 || !is_definition(line, config) {
+            continue;
+        }
+
+        let mut start = index;
+        while start > 0 && depths[start - 1] == 0 && is_attached(&lines[start - 1]) {
+            start -= 1;
+        }
+        item_start[start] = true;
+
+        let end = (index..lines.len())
+            .find(|e| depths.get(e + 1).is_none_or(|d| *d == 0))
+            .unwrap_or(index);
+        item_end[end] = true;
+    }
+
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut blank = 0;
+    let mut prev: Option<usize> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            blank += 1;
+            continue;
+        }
+
+        let mut keep = blank.min(config.max_blank_lines);
+        if let Some(prev) = prev {
+            let opens_block = lines[prev].last().is_some_and(|e| {
+                matches!(
+                    e,
+                    Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen
+                )
+            });
+            let closes_block = line.first().is_some_and(|e| {
+                matches!(
+                    e,
+                    Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose
+                )
+            });
+            if config.trim_block_blank_lines && (opens_block || closes_block) {
+                keep = 0;
+            }
+            if item_start[index] && item_end[prev] {
+                keep = 1;
+            }
+
+            out.push(Token::NewLine);
+            out.extend(std::iter::repeat_n(Token::NewLine, keep));
+        }
+
+        out.extend(line.iter().cloned());
+        prev = Some(index);
+        blank = 0;
+    }
+    out
+}
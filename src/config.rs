@@ -0,0 +1,153 @@
+use clap::{ArgAction, Args, Parser, ValueEnum};
+
+#[derive(Args, Debug, Clone)]
+pub struct Config {
+    /// Indent with tabs or spaces
+    #[arg(long, value_enum, default_value_t = IndentStyle::Tabs)]
+    pub indent_style: IndentStyle,
+
+    /// Tabs or spaces making up one level of indentation
+    #[arg(long, default_value_t = 2)]
+    pub indent_width: usize,
+
+    /// Separator placed between list items
+    #[arg(long, value_enum, default_value_t = ListSeparator::Preserve)]
+    pub list_separator: ListSeparator,
+
+    /// Add a space inside parentheses: `( $a + 1 )` instead of `($a + 1)`
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub paren_padding: bool,
+
+    /// Add a space inside square brackets: `[ 1 2 ]` instead of `[1 2]`
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub square_padding: bool,
+
+    /// Add a space inside the braces of a block: `{ ls }` instead of `{ls}`
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub block_padding: bool,
+
+    /// Add a space inside the braces of a record: `{ a: 1 }` instead of `{a: 1}`
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub record_padding: bool,
+
+    /// Case of filesize units: `10kb`, `1.5GiB`
+    #[arg(long, value_enum, default_value_t = Case::Preserve)]
+    pub unit_case: Case,
+
+    /// Case of the digits of hexadecimal numbers: `0xff`
+    #[arg(long, value_enum, default_value_t = Case::Preserve)]
+    pub hex_case: Case,
+
+    /// End the output with a newline
+    #[arg(long, value_enum, default_value_t = FinalNewline::Preserve)]
+    pub final_newline: FinalNewline,
+
+    /// Line endings of the output, `auto` keeps the ones of the input
+    #[arg(long, value_enum, default_value_t = LineEnding::Auto)]
+    pub line_ending: LineEnding,
+
+    /// Runs of blank lines are cut down to this many
+    #[arg(long, default_value_t = 1)]
+    pub max_blank_lines: usize,
+
+    /// Remove blank lines at the start and end of blocks
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub trim_block_blank_lines: bool,
+
+    /// Sort and dedupe runs of `use`, `source` and `overlay use` statements
+    #[arg(long)]
+    pub sort_imports: bool,
+
+    /// Where `else` goes after the closing brace of the previous branch
+    #[arg(long, value_enum, default_value_t = ElsePlacement::SameLine)]
+    pub else_placement: ElsePlacement,
+
+    /// Line up the `=>` of the arms of a multi-line match
+    #[arg(long)]
+    pub align_match_arms: bool,
+
+    /// End lists that are broken over multiple lines with a comma
+    #[arg(long)]
+    pub trailing_comma: bool,
+
+    /// Lists that would grow past this width are broken one item per line
+    #[arg(long, default_value_t = 100)]
+    pub max_width: usize,
+
+    /// Lex the input as a NUON document, set by the NUON mode rather than a flag
+    #[arg(skip)]
+    pub nuon: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListSeparator {
+    /// Always separate list items with a comma
+    Commas,
+    /// Only separate list items with whitespace
+    Spaces,
+    /// Keep whatever the input used
+    Preserve,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalNewline {
+    Always,
+    Never,
+    /// Only when the input ends with one
+    Preserve,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Whatever the first line of the input ends with
+    Auto,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElsePlacement {
+    /// `} else {`
+    SameLine,
+    /// `}` and `else {` on the next line
+    NextLine,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Upper,
+    /// Keep whatever the input used
+    Preserve,
+}
+
+impl Config {
+    // the indentation of a line `depth` levels deep
+    pub fn indent(&self, depth: usize) -> String {
+        let unit = match self.indent_style {
+            IndentStyle::Tabs => "\t",
+            IndentStyle::Spaces => " ",
+        };
+        unit.repeat(depth * self.indent_width)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // the defaults live on the clap attributes, parsing an empty command line keeps them in
+        // one place
+        #[derive(Parser)]
+        struct Defaults {
+            #[command(flatten)]
+            config: Config,
+        }
+
+        Defaults::parse_from(["nushell_fmt"]).config
+    }
+}
@@ -0,0 +1,125 @@
+use similar::{Algorithm, DiffOp, TextDiff};
+
+// Replaces the bytes `start..end` of the original text with `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl Edit {
+    // The edit as an LSP `TextEdit`, with the byte offsets next to the range
+    pub fn to_json(&self, text: &str) -> serde_json::Value {
+        let (start_line, start_character) = position(text, self.start);
+        let (end_line, end_character) = position(text, self.end);
+
+        serde_json::json!({
+            "range": {
+                "start": { "line": start_line, "character": start_character },
+                "end": { "line": end_line, "character": end_character },
+            },
+            "newText": self.text,
+            "start": self.start,
+            "end": self.end,
+        })
+    }
+}
+
+// Line and column of the byte `offset`, counted from 0 with the column in UTF-16 code units as
+// LSP does
+pub fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|e| e + 1).unwrap_or_default();
+
+    (line, before[line_start..].encode_utf16().count())
+}
+
+// byte offset of every char and of the end of `text`
+fn char_offsets(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(i, _)| i)
+        .chain([text.len()])
+        .collect()
+}
+
+// byte offset of the start of every line and of the end of `text`
+fn line_offsets(text: &str) -> Vec<usize> {
+    let mut offsets = vec![0];
+    offsets.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    if offsets.last() != Some(&text.len()) {
+        offsets.push(text.len());
+    }
+    offsets
+}
+
+// edits within a changed run of lines, `base` is where `old` starts in the original
+fn char_edits(old: &str, new: &str, base: usize, edits: &mut Vec<Edit>) {
+    let old_offsets = char_offsets(old);
+    let new_offsets = char_offsets(new);
+
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_chars(old, new);
+    for op in diff.ops() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let (old_range, new_range) = (op.old_range(), op.new_range());
+        let start = base + old_offsets[old_range.start];
+        let end = base + old_offsets[old_range.end];
+        let text = &new[new_offsets[new_range.start]..new_offsets[new_range.end]];
+
+        // a delete followed by an insert at the same place is one replacement
+        match edits.last_mut() {
+            Some(last) if last.end == start => {
+                last.end = end;
+                last.text.push_str(text);
+            }
+            _ => edits.push(Edit {
+                start,
+                end,
+                text: text.to_string(),
+            }),
+        }
+    }
+}
+
+// The smallest edits turning `old` into `new`. The lines are diffed first and the changed lines
+// are then diffed by char, so reindenting a line only touches its leading whitespace
+pub fn diff(old: &str, new: &str) -> Vec<Edit> {
+    let old_lines = line_offsets(old);
+    let new_lines = line_offsets(new);
+
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_lines(old, new);
+
+    let mut edits = Vec::new();
+    for op in diff.ops() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let (old_range, new_range) = (op.old_range(), op.new_range());
+        let old_start = old_lines[old_range.start];
+        let old_end = old_lines[old_range.end];
+        let new_text = &new[new_lines[new_range.start]..new_lines[new_range.end]];
+
+        char_edits(&old[old_start..old_end], new_text, old_start, &mut edits);
+    }
+    edits
+}
+
+// Applies edits sorted by position that don't overlap
+pub fn apply(text: &str, edits: &[Edit]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut index = 0;
+    for edit in edits {
+        out.push_str(&text[index..edit.start]);
+        out.push_str(&edit.text);
+        index = edit.end;
+    }
+    out.push_str(&text[index..]);
+    out
+}
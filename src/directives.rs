@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+use crate::{
+    error::{FormatError, Span},
+    range::statements,
+    validate::{structure, Structure},
+};
+
+// stands in for a region that is left alone while the rest of the file is formatted, a NUL is
+// only valid inside a string or comment and the lexer never looks at those
+pub const PLACEHOLDER: char = '\u{0}';
+
+// `# nufmt: off` into `off`
+fn directive(line: &str) -> Option<&str> {
+    let comment = line.trim().strip_prefix('#')?;
+    comment.trim_start().strip_prefix("nufmt:").map(str::trim)
+}
+
+// end of the line starting at `start`, before the `\n` or `\r\n`
+fn line_end(buffer: &str, start: usize) -> usize {
+    let end = buffer[start..]
+        .find('\n')
+        .map(|e| start + e)
+        .unwrap_or(buffer.len());
+    match buffer[..end].ends_with('\r') {
+        true => end - 1,
+        false => end,
+    }
+}
+
+// The regions that aren't formatted, whole lines from `# nufmt: off` to `# nufmt: on` or the
+// end of the block it is in, and from `# nufmt: skip` to the end of the statement after it
+fn verbatim_regions(buffer: &str, structure: Option<&Structure>) -> Vec<Range<usize>> {
+    let starts = std::iter::once(0)
+        .chain(buffer.match_indices('\n').map(|(i, _)| i + 1))
+        .filter(|e| *e < buffer.len())
+        .collect::<Vec<_>>();
+    let line = |i: usize| &buffer[starts[i]..line_end(buffer, starts[i])];
+
+    let mut regions = Vec::new();
+    let mut index = 0;
+    while index < starts.len() {
+        let last = match directive(line(index)) {
+            Some("off") => {
+                // the line before the one closing the innermost block around the directive
+                let block_end = structure
+                    .and_then(|e| {
+                        e.pairs
+                            .iter()
+                            .filter(|(open, close)| *open < starts[index] && *close > starts[index])
+                            .min_by_key(|(_, close)| *close)
+                    })
+                    .map(|(_, close)| starts.iter().rposition(|e| e <= close).unwrap_or(index))
+                    .map(|e| e.saturating_sub(1).max(index))
+                    .unwrap_or(starts.len() - 1);
+                // an `on` after the end of the block makes a region that cuts the block in half
+                let next = (index + 1..starts.len())
+                    .find(|e| matches!(directive(line(*e)), Some("on" | "off")));
+                match next {
+                    Some(next) if directive(line(next)) == Some("on") => next,
+                    _ => block_end,
+                }
+            }
+            Some("skip") => {
+                let Some(next) = (index + 1..starts.len()).find(|e| !line(*e).trim().is_empty())
+                else {
+                    index += 1;
+                    continue;
+                };
+                // a file that doesn't parse, or a directive inside a list, still skips the line
+                // after the directive
+                let end = statements(buffer, starts[next], line_end(buffer, starts[next]))
+                    .ok()
+                    .filter(|e| e.start >= starts[next])
+                    .map_or(starts[next], |e| e.end);
+                starts.iter().rposition(|e| *e <= end).unwrap_or(next)
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+
+        regions.push(starts[index]..line_end(buffer, starts[last]));
+        index = last + 1;
+    }
+    regions
+}
+
+// the first delimiter in `region` that is matched by one outside of it
+fn cut_delimiter(region: &Range<usize>, structure: &Structure) -> Option<usize> {
+    structure
+        .pairs
+        .iter()
+        .filter(|(open, close)| region.contains(open) != region.contains(close))
+        .map(|(open, close)| match region.contains(open) {
+            true => *open,
+            false => *close,
+        })
+        .min()
+}
+
+// Checks no region formatting is turned off for opens or closes a block it doesn't end in,
+// the formatter needs the delimiters of a region to know the depth of what follows it
+pub fn check(buffer: &str, structure: &Structure) -> Result<(), FormatError> {
+    for region in verbatim_regions(buffer, Some(structure)) {
+        if let Some(index) = cut_delimiter(&region, structure) {
+            let delimiter = buffer[index..].chars().next().unwrap_or_default();
+            return Err(FormatError::CutByDirective {
+                delimiter,
+                span: Span::new(index, index + 1),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Swaps every region formatting is disabled for with a placeholder, the lexer turns each of
+// them in a token holding the text of the region. A region cutting a block in half is
+// formatted like the rest
+pub fn protect(buffer: &str) -> (String, Vec<Range<usize>>) {
+    let structure = structure(buffer).ok();
+    let regions = verbatim_regions(buffer, structure.as_ref())
+        .into_iter()
+        .filter(|region| {
+            structure
+                .as_ref()
+                .is_none_or(|e| cut_delimiter(region, e).is_none())
+        })
+        .collect::<Vec<_>>();
+
+    let mut out = String::with_capacity(buffer.len());
+    let mut index = 0;
+    for region in &regions {
+        out.push_str(&buffer[index..region.start]);
+        out.push(PLACEHOLDER);
+        index = region.end;
+    }
+    out.push_str(&buffer[index..]);
+
+    (out, regions)
+}
@@ -0,0 +1,154 @@
+use std::fmt::Display;
+
+// Byte range in the source, `end` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    // 1 based line and column (in chars) of the start of the span
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|e| e + 1).unwrap_or_default();
+        let col = before[line_start..].chars().count() + 1;
+
+        (line, col)
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    // a quote that is never closed, the span covers the opening quote
+    UnterminatedString {
+        quote: char,
+        span: Span,
+    },
+    // `(` closed by `]` has both, an unclosed `(` has no close and a stray `)` has no open
+    UnbalancedDelimiter {
+        open: Option<(char, Span)>,
+        close: Option<(char, Span)>,
+    },
+    // a character that can't appear outside of a string, like a control character
+    InvalidToken {
+        token: char,
+        span: Span,
+    },
+    // a command, pipeline or variable in a NUON document, which holds data only
+    NotData {
+        found: String,
+        span: Span,
+    },
+    // a `# nufmt: off` region that opens or closes a block it doesn't end in
+    CutByDirective {
+        delimiter: char,
+        span: Span,
+    },
+    // a range of lines or bytes to format that starts after the end of the file
+    OutsideFile {
+        start: usize,
+        end: usize,
+        len: usize,
+        unit: &'static str,
+    },
+    Io(std::io::Error),
+}
+
+impl FormatError {
+    // the places in the source the error points at with a short label for each
+    pub fn labels(&self) -> Vec<(Span, String)> {
+        match self {
+            FormatError::UnterminatedString { span, .. } => {
+                vec![(*span, "string starts here".to_string())]
+            }
+            FormatError::UnbalancedDelimiter { open, close } => {
+                let mut labels = Vec::new();
+                if let Some((c, span)) = open {
+                    labels.push((*span, format!("`{}` opened here", c)));
+                }
+                match close {
+                    Some((c, span)) if open.is_some() => {
+                        labels.push((*span, format!("`{}` closes it here", c)))
+                    }
+                    Some((c, span)) => labels.push((*span, format!("`{}` was never opened", c))),
+                    None => (),
+                }
+                labels
+            }
+            FormatError::InvalidToken { span, .. } => vec![(*span, "found here".to_string())],
+            FormatError::NotData { span, .. } => {
+                vec![(*span, "NUON only holds data".to_string())]
+            }
+            FormatError::CutByDirective { span, .. } => {
+                vec![(*span, "inside the region".to_string())]
+            }
+            FormatError::OutsideFile { .. } | FormatError::Io(_) => Vec::new(),
+        }
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::UnterminatedString { quote, .. } => {
+                write!(f, "unterminated string, missing closing `{}`", quote)
+            }
+            FormatError::UnbalancedDelimiter {
+                open: Some((open, _)),
+                close: Some((close, _)),
+            } => write!(f, "unbalanced delimiter, `{}` closed by `{}`", open, close),
+            FormatError::UnbalancedDelimiter {
+                open: Some((open, _)),
+                close: None,
+            } => write!(f, "unbalanced delimiter, `{}` is never closed", open),
+            FormatError::UnbalancedDelimiter {
+                close: Some((close, _)),
+                ..
+            } => write!(f, "unbalanced delimiter, unexpected `{}`", close),
+            FormatError::UnbalancedDelimiter { .. } => write!(f, "unbalanced delimiter"),
+            FormatError::InvalidToken { token, .. } => {
+                write!(f, "invalid token {:?}", token)
+            }
+            FormatError::NotData { found, .. } => {
+                write!(f, "not a data document, found `{}`", found)
+            }
+            FormatError::CutByDirective { delimiter, .. } => write!(
+                f,
+                "formatting is turned off for part of a block, `{}` is matched outside the region",
+                delimiter
+            ),
+            FormatError::OutsideFile {
+                start,
+                end,
+                len,
+                unit,
+            } => write!(
+                f,
+                "the range {}:{} is outside the file, which ends at {} {}",
+                start, end, unit, len
+            ),
+            FormatError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(value: std::io::Error) -> Self {
+        FormatError::Io(value)
+    }
+}
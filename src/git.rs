@@ -0,0 +1,65 @@
+use std::{path::Path, process::Command};
+
+use crate::error::FormatError;
+
+// `@@ -3,2 +4,3 @@ def a` into the changed lines on the new side, (4, 6)
+fn hunk_lines(header: &str) -> Option<(usize, usize)> {
+    let new = header
+        .strip_prefix("@@ ")?
+        .split(' ')
+        .find(|e| e.starts_with('+'))?;
+    let (start, count) = new[1..].split_once(',').unwrap_or((&new[1..], "1"));
+    let (start, count) = (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?);
+
+    // only removed lines, nothing left to format
+    if count == 0 {
+        return None;
+    }
+    Some((start, start + count - 1))
+}
+
+// The lines added or changed by the hunks of a `git diff --unified=0`, counted from 1
+pub fn parse_hunks(diff: &str) -> Vec<(usize, usize)> {
+    diff.lines()
+        .filter(|e| e.starts_with("@@ "))
+        .filter_map(hunk_lines)
+        .collect()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<std::process::Output, FormatError> {
+    Ok(Command::new("git").arg("-C").arg(dir).args(args).output()?)
+}
+
+// The lines of `path` changed in the working tree since `rev`, an untracked file is changed
+// as a whole
+pub fn changed_lines(path: &Path, rev: &str) -> Result<Vec<(usize, usize)>, FormatError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    let diff = git(
+        dir,
+        &[
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--unified=0",
+            rev,
+            "--",
+            &name,
+        ],
+    )?;
+    if !diff.status.success() {
+        let message = String::from_utf8_lossy(&diff.stderr).trim().to_string();
+        return Err(std::io::Error::other(format!("git diff failed: {}", message)).into());
+    }
+
+    let tracked = git(dir, &["ls-files", "--error-unmatch", "--", &name])?;
+    if !tracked.status.success() {
+        return Ok(vec![(1, usize::MAX)]);
+    }
+
+    Ok(parse_hunks(&String::from_utf8_lossy(&diff.stdout)))
+}
@@ -0,0 +1,83 @@
+use crate::{
+    config::{Config, ElsePlacement},
+    tokens::Token,
+};
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w == word)
+}
+
+// Index of the `{` opening the body of the `if` at `start`, None for the guard of a match arm
+// `_ if $x => 0` or an `if` without a body
+fn body_open(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(start + 1) {
+        match token {
+            Token::BraceOpen if depth == 0 => return Some(index),
+            Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen => depth += 1,
+            Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => depth -= 1,
+            Token::FatArrow if depth == 0 => return None,
+            // the body may start on the next line `if $a\n{`
+            Token::NewLine if depth == 0 => {
+                let open = index + tokens[index..].iter().position(|e| *e != Token::NewLine)?;
+                return (tokens[open] == Token::BraceOpen).then_some(open);
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// Puts `else` and `else if` next to the closing brace of the previous branch, or on the line
+// after it, and pulls the opening brace of every branch up to its condition
+pub fn normalise_if_else(tokens: Vec<Token>, config: &Config) -> Vec<Token> {
+    let mut drop = vec![false; tokens.len()];
+    let mut newline_before = vec![false; tokens.len()];
+
+    for (index, token) in tokens.iter().enumerate() {
+        if is_word(token, "if") {
+            if let Some(open) = body_open(&tokens, index) {
+                (index + 1..open)
+                    .rev()
+                    .take_while(|i| tokens[*i] == Token::NewLine)
+                    .for_each(|i| drop[i] = true);
+            }
+        }
+
+        if !is_word(token, "else") {
+            continue;
+        }
+
+        // newlines between `}` and `else`
+        let newlines = tokens[..index]
+            .iter()
+            .rev()
+            .take_while(|e| **e == Token::NewLine)
+            .count();
+        if tokens[..index - newlines].last() == Some(&Token::BraceClose) {
+            (index - newlines..index).for_each(|i| drop[i] = true);
+            newline_before[index] = config.else_placement == ElsePlacement::NextLine;
+        }
+
+        // newlines between `else` and `{` or `if`
+        let after = tokens[index + 1..]
+            .iter()
+            .take_while(|e| **e == Token::NewLine)
+            .count();
+        let next = tokens.get(index + 1 + after);
+        if next == Some(&Token::BraceOpen) || next.is_some_and(|e| is_word(e, "if")) {
+            (index + 1..index + 1 + after).for_each(|i| drop[i] = true);
+        }
+    }
+
+    let mut out = Vec::with_capacity(tokens.len());
+    for (index, token) in tokens.into_iter().enumerate() {
+        if newline_before[index] {
+            out.push(Token::NewLine);
+        }
+        if !drop[index] {
+            out.push(token);
+        }
+    }
+    out
+}
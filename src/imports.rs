@@ -0,0 +1,181 @@
+use crate::{error::Span, tokens::Token};
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w == word)
+}
+
+// number of tokens making up the keyword of an import `use`, `export use`, `overlay use`,
+// `source`, `source-env`
+fn import_keyword(line: &[Token]) -> Option<usize> {
+    let first = line.first();
+    if is_word(first, "use") || is_word(first, "source") || is_word(first, "source-env") {
+        return Some(1);
+    }
+    if (is_word(first, "overlay") || is_word(first, "export")) && is_word(line.get(1), "use") {
+        return Some(2);
+    }
+    None
+}
+
+// the standard library goes first, relative paths last
+fn tier(path: &str) -> usize {
+    let path = path.trim_matches(['"', '\'', '`']);
+    if path == "std" || path.starts_with("std/") {
+        0
+    } else if path.starts_with('.') {
+        2
+    } else {
+        1
+    }
+}
+
+// a line of tokens and where in the source the ones that aren't layout come from, the spans
+// follow the tokens when the lines are sorted
+#[derive(Debug, Clone)]
+struct Line {
+    tokens: Vec<Token>,
+    spans: Vec<Option<Span>>,
+}
+
+// `use std [log assert]` becomes `use std [assert log]`
+fn sort_members(line: &mut Line) {
+    let Some(open) = line
+        .tokens
+        .iter()
+        .position(|e| e == &Token::BraceSquareOpen)
+    else {
+        return;
+    };
+    let Some(close) = line
+        .tokens
+        .iter()
+        .rposition(|e| e == &Token::BraceSquareClosed)
+    else {
+        return;
+    };
+    if close < open {
+        return;
+    }
+
+    let members = &line.tokens[open + 1..close];
+    // a comment belongs to the member next to it
+    if members
+        .iter()
+        .any(|e| matches!(e, Token::CommentBlock(_) | Token::Verbatim(_)))
+    {
+        return;
+    }
+    let commas = members.contains(&Token::Comma);
+    let multiline = members.contains(&Token::NewLine);
+    let mut names = members
+        .iter()
+        .zip(&line.spans[open + 1..close])
+        .filter(|(e, _)| !e.is_layout())
+        .map(|(e, span)| (e.clone(), *span))
+        .collect::<Vec<_>>();
+    names.sort_by_key(|(e, _)| e.as_string().to_lowercase());
+    names.dedup_by(|a, b| a.0 == b.0);
+
+    let mut sorted = Line {
+        tokens: Vec::with_capacity(members.len()),
+        spans: Vec::with_capacity(members.len()),
+    };
+    for (index, (name, span)) in names.into_iter().enumerate() {
+        if commas && index > 0 {
+            sorted.tokens.push(Token::Comma);
+            sorted.spans.push(None);
+        }
+        // a list written one member per line stays that way
+        if multiline {
+            sorted.tokens.push(Token::NewLine);
+            sorted.spans.push(None);
+        }
+        sorted.tokens.push(name);
+        sorted.spans.push(span);
+    }
+    if multiline {
+        sorted.tokens.push(Token::NewLine);
+        sorted.spans.push(None);
+    }
+    line.tokens.splice(open + 1..close, sorted.tokens);
+    line.spans.splice(open + 1..close, sorted.spans);
+}
+
+// brackets left open at the end of `line`
+fn depth(line: &[Token]) -> isize {
+    line.iter()
+        .map(|e| match e {
+            Token::BraceSquareOpen | Token::BraceOpen | Token::ParenOpen => 1,
+            Token::BraceSquareClosed | Token::BraceClose | Token::ParenClose => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn sort_key(line: &[Token]) -> (usize, String, String) {
+    let keyword = import_keyword(line).unwrap_or_default();
+    let path = line.get(keyword).map(|e| e.as_string()).unwrap_or_default();
+    let text = line.iter().map(|e| e.as_string()).collect::<String>();
+
+    (tier(&path), path.to_lowercase(), text)
+}
+
+// Sorts every run of import statements on consecutive lines, removing duplicates. `spans` are
+// the spans of the tokens that aren't layout, they are returned in the new order of the tokens
+pub fn sort_imports(tokens: Vec<Token>, spans: Vec<Span>) -> (Vec<Token>, Vec<Span>) {
+    let mut spans = spans.into_iter();
+    let spans = tokens
+        .iter()
+        .map(|e| match e.is_layout() {
+            true => None,
+            false => spans.next(),
+        })
+        .collect::<Vec<_>>();
+
+    // a statement runs on until its brackets balance `use std [\n\tlog\n]`
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in (0..=tokens.len()).filter(|e| tokens.get(*e).is_none_or(|e| e == &Token::NewLine)) {
+        if end < tokens.len() && depth(&tokens[start..end]) > 0 {
+            continue;
+        }
+        lines.push(Line {
+            tokens: tokens[start..end].to_vec(),
+            spans: spans[start..end].to_vec(),
+        });
+        start = end + 1;
+    }
+
+    let mut index = 0;
+    while index < lines.len() {
+        let end = index
+            + lines[index..]
+                .iter()
+                .take_while(|e| import_keyword(&e.tokens).is_some())
+                .count();
+        if end == index {
+            index += 1;
+            continue;
+        }
+
+        let block = &mut lines[index..end];
+        block.iter_mut().for_each(sort_members);
+        block.sort_by_cached_key(|e| sort_key(&e.tokens));
+
+        let mut sorted = block.to_vec();
+        sorted.dedup_by(|a, b| a.tokens == b.tokens);
+        let removed = block.len() - sorted.len();
+        lines.splice(index..end, sorted);
+
+        index = end - removed;
+    }
+
+    let spans = lines.iter().flat_map(|e| e.spans.iter().flatten().copied());
+    let spans = spans.collect();
+    let tokens = lines
+        .into_iter()
+        .map(|e| e.tokens)
+        .collect::<Vec<_>>()
+        .join(&Token::NewLine);
+    (tokens, spans)
+}
@@ -0,0 +1,696 @@
+//! Formatter for Nushell scripts. The CLI in `main.rs` is a thin wrapper around [`format_str`].
+
+#[cfg(test)]
+mod test;
+
+mod blank_lines;
+pub mod config;
+pub mod diff;
+mod directives;
+mod error;
+pub mod git;
+mod if_else;
+mod imports;
+mod list;
+pub mod lsp;
+pub mod markdown;
+mod match_block;
+mod number;
+pub mod nuon;
+mod range;
+#[allow(unused)]
+mod tokenizer;
+pub mod tokens;
+mod validate;
+
+use std::ops::Range;
+
+use blank_lines::normalise_blank_lines;
+use config::{FinalNewline, LineEnding};
+use if_else::normalise_if_else;
+use imports::sort_imports;
+use list::normalise_lists;
+use match_block::{align_match_arms, normalise_match_arms};
+use number::normalise_number;
+use tokenizer::Tokonizer;
+use tokens::{TokonizerTools, GENERIC_TYPES};
+
+pub use config::Config;
+pub use error::{FormatError, Span};
+pub use range::{
+    format_lines, format_lines_with_cursor, format_range, format_range_with_cursor, line_range,
+};
+pub use tokens::Token;
+
+// Where every token that isn't layout is in `buffer`, the lexer keeps the text of those tokens
+// so they are found one after the other with only layout in between
+fn token_spans(buffer: &str, tokens: &[Token]) -> Vec<Span> {
+    let mut index = 0;
+    tokens
+        .iter()
+        .filter(|e| !e.is_layout())
+        .map(|e| {
+            let text = match e {
+                Token::Verbatim(_) => directives::PLACEHOLDER.to_string(),
+                _ => e.as_string(),
+            };
+            let start = buffer[index..].find(&text).map(|e| index + e).filter(|e| {
+                buffer[index..*e]
+                    .chars()
+                    .all(|e| e.is_whitespace() || e == ',')
+            });
+            match start {
+                Some(start) => {
+                    index = start + text.len();
+                    Span::new(start, index)
+                }
+                None => Span::new(index, index),
+            }
+        })
+        .collect()
+}
+
+// The tokens of `buffer` and the spans of the ones that aren't layout in the same order.
+// `verbatim` holds the text of the regions swapped for placeholders by `directives::protect`
+fn gen_tokens(buffer: String, config: &Config, verbatim: &[&str]) -> (Vec<Token>, Vec<Span>) {
+    let tokens = buffer.chars().map(Token::from).collect::<Vec<_>>();
+    let mut t = Tokonizer::new(tokens);
+    let mut verbatim = verbatim.iter();
+
+    while let Some(token) = t.get() {
+        match token {
+            // the arrow of a match arm `_ => 0`
+            Token::Equals if t.next_eq(Token::MoreThen) => {
+                t.temp_to_word_or_number();
+                t.next();
+
+                t.to_stack(Token::FatArrow);
+            }
+            Token::Equals if t.is_env_shorthand() => {
+                let env = t.take_env_shorthand();
+
+                t.to_stack(Token::EnvShorthand(env));
+            }
+            Token::MoreThen if t.is_redirect() => {
+                let redirect = t.take_redirect();
+
+                t.to_stack(Token::Redirect(redirect));
+            }
+            _ if t.operator_at().is_some() => {
+                let op = t.operator_at().unwrap_or_default();
+                t.temp_to_word_or_number();
+                // the first character is the current token
+                t.index += op.len() - 1;
+
+                t.to_stack(Token::Operator(op.to_string()));
+            }
+            Token::Char('.') if t.is_range_start() => {
+                let range = t.take_range();
+
+                t.to_stack(Token::Range(range));
+            }
+            // `(ls).name`, `[1 2].0`
+            Token::Char('.')
+                if t.temp.is_empty()
+                    && t.next_is_name_start()
+                    && matches!(
+                        t.stack.last(),
+                        Some(Token::ParenClose)
+                            | Some(Token::BraceSquareClosed)
+                            | Some(Token::BraceClose)
+                    ) =>
+            {
+                let path = t.take_cell_path();
+
+                t.to_stack(Token::CellPath(path));
+            }
+            // external command with a quoted name `^"my app"`
+            Token::Char('^')
+                if t.temp.is_empty()
+                    && (t.next_eq(Token::DoubleQuote) || t.next_eq(Token::SingleQuote)) =>
+            {
+                t.next();
+                let quote = t.get().unwrap_or(Token::DoubleQuote);
+                let block = t.take_upto(&[quote], true);
+
+                t.to_stack(Token::External(format!("^{}", block)));
+            }
+            Token::Char(directives::PLACEHOLDER) if t.temp.is_empty() => match verbatim.next() {
+                Some(region) => t.to_stack(Token::Verbatim(region.to_string())),
+                None => t.to_temp(token.as_string()),
+            },
+            Token::Char(_) => t.to_temp(token.as_string()),
+            // a binary literal `0x[ff 00]`, `0b[1010]` is a single number
+            Token::BraceSquareOpen if matches!(t.temp.as_str(), "0x" | "0o" | "0b") => {
+                let bytes = t.take_upto(&[Token::BraceSquareClosed], true);
+
+                t.to_stack(Token::Number(format!("{}{}", t.temp, bytes)));
+                t.temp.clear();
+            }
+            // in NUON an url `http://a.com` or drive `C:/Users` is a path, a record value
+            // `{ a: /tmp }` isn't
+            Token::Colon if config.nuon && !t.temp.is_empty() && t.next_eq(Token::Slash) => {
+                t.to_temp(token.as_string())
+            }
+            // the time of a datetime `2024-01-01T10:00:00`
+            Token::Colon if t.is_datetime_colon() => t.to_temp(token.as_string()),
+            Token::WhiteSpace
+            | Token::NewLine
+            | Token::Pipe
+            | Token::Comma
+            | Token::Equals
+            | Token::ParenOpen
+            | Token::MoreThen
+            | Token::LessThen
+            | Token::Exc
+            | Token::ParenClose
+            | Token::BraceOpen
+            | Token::BraceClose
+            | Token::BraceSquareOpen
+            | Token::BraceSquareClosed
+            | Token::Tilda
+            | Token::Colon
+            | Token::Semicolon => {
+                // move word to stack
+                t.temp_to_word_or_number();
+
+                // push current to stack
+                t.to_stack(token);
+            }
+            // a dash inside a word is part of it: `str-join`, `$a-b`
+            Token::Dash if !t.temp.is_empty() => t.to_temp(token.as_string()),
+            // a dash directly followed by a digit is a negative number `-1`
+            Token::Dash if matches!(t.peak_next(), Some(Token::Char(c)) if c.is_ascii_digit()) => {
+                t.to_temp(token.as_string())
+            }
+            // a dash right after a subexpression is a subtraction `(1)-(2)`
+            Token::Dash if t.prev_eq(Token::ParenClose) => {
+                t.to_stack(Token::Operator(token.as_string()))
+            }
+            Token::Dash if t.is_flag_start() => {
+                let flag = t.take_flag();
+
+                match flag.starts_with("--") {
+                    true => t.to_stack(Token::TagLong(flag)),
+                    false => t.to_stack(Token::Tag(flag)),
+                }
+            }
+            // an external command run from a variable `^$cmd` keeps its caret
+            Token::Dolar if t.next_is_name_start() && t.temp == "^" => {
+                t.temp.clear();
+                t.next();
+
+                let path = t.take_cell_path();
+                t.to_stack(Token::External(format!("^${}", path)));
+            }
+            Token::Dolar if t.next_is_name_start() => {
+                t.temp_to_word_or_number();
+                t.next();
+
+                let path = t.take_cell_path();
+                t.to_stack(Token::Variable(format!("${}", path)));
+            }
+            Token::DoubleQuote => {
+                // take tokens upto DoubleQuote and add them to the stack
+                let block = t.take_upto(&[Token::DoubleQuote], true);
+
+                t.to_stack(Token::DoubleQuoteBlock(block));
+            }
+            Token::SingleQuote => {
+                // take tokens upto SingleQuote and add them to the stack
+                let block = t.take_upto(&[Token::SingleQuote], true);
+
+                t.to_stack(Token::SingleQuoteBlock(block));
+            }
+            // a path right after a variable is part of it `$env.PWD/foo`
+            Token::Slash
+                if t.temp.is_empty()
+                    && matches!(t.stack.last(), Some(Token::Variable(_)) | Some(Token::CellPath(_))) =>
+            {
+                let block = t.take_path(config.nuon);
+
+                match t.stack.pop() {
+                    Some(Token::CellPath(path)) => t.to_stack(Token::CellPath(path + &block)),
+                    Some(Token::Variable(path)) => t.to_stack(Token::Variable(path + &block)),
+                    _ => (),
+                }
+            }
+            // a path ends at the whitespace or delimiter after it `{ cd /tmp}`
+            Token::Slash => {
+                let block = t.take_path(config.nuon);
+
+                t.to_stack(Token::Path(format!("{}{}", t.temp, block)));
+                t.temp.clear();
+            }
+            Token::Att=> {
+                let block = t
+                    .take_upto(&[Token::NewLine], false)
+                    .trim_end()
+                    .to_string();
+
+                t.to_stack(Token::AttSomething(format!("{}{}", t.temp, block)));
+                t.temp.clear();
+            }
+
+            // a raw string `r#'it's'#` is a single string
+            Token::Hash if t.is_raw_string_start() => {
+                let block = t.take_raw_string();
+
+                t.to_stack(Token::SingleQuoteBlock(format!("{}{}", t.temp, block)));
+                t.temp.clear();
+            }
+            // Commant block
+            Token::Hash => {
+                // If line starts with a hash its a commant block.. walking upto new line and but
+                // the line in a commant_block token. formatting is ignored on this line, commant
+                // indent is still applied
+                let block = t.take_upto(&[Token::NewLine], false);
+
+                t.to_stack(Token::CommentBlock(block));
+            }
+
+            _ => {
+                // push current to stack
+                t.to_stack(token);
+            }
+        }
+        t.next();
+    }
+
+    t.temp_to_word_or_number();
+    let mut spans = token_spans(&buffer, &t.stack);
+    let tokens = normalise_lists(t.stack, config)
+        .into_iter()
+        .filter(|e| e != &Token::WhiteSpace && e != &Token::Tab(0))
+        .map(|e| match e {
+            Token::Number(n) => Token::Number(normalise_number(&n, config)),
+            _ => e,
+        })
+        .collect::<Vec<_>>();
+
+    let mut tokens = normalise_if_else(normalise_match_arms(tokens), config);
+    if config.sort_imports {
+        (tokens, spans) = sort_imports(tokens, spans);
+    }
+    (normalise_blank_lines(tokens, config), spans)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delimiter {
+    Paren,
+    Square,
+    Block,
+    Record,
+}
+
+impl Delimiter {
+    fn padded(&self, config: &Config) -> bool {
+        match self {
+            Delimiter::Paren => config.paren_padding,
+            Delimiter::Square => config.square_padding,
+            Delimiter::Block => config.block_padding,
+            Delimiter::Record => config.record_padding,
+        }
+    }
+}
+
+// a brace opens a record when the first thing inside it is a key followed by a colon
+fn is_record(t: &Tokonizer) -> bool {
+    let mut inner = t.tokens[t.index + 1..]
+        .iter()
+        .filter(|e| *e != &Token::NewLine);
+
+    match inner.next() {
+        Some(Token::BraceClose) => true,
+        Some(Token::Word(_))
+        | Some(Token::Number(_))
+        | Some(Token::DoubleQuoteBlock(_))
+        | Some(Token::SingleQuoteBlock(_)) => inner.next() == Some(&Token::Colon),
+        _ => false,
+    }
+}
+
+fn add_delimiter(t: &Tokonizer, token: &Token, delimiters: &mut Vec<Delimiter>) {
+    match token {
+        Token::ParenOpen => delimiters.push(Delimiter::Paren),
+        Token::BraceSquareOpen => delimiters.push(Delimiter::Square),
+        Token::BraceOpen if is_record(t) => delimiters.push(Delimiter::Record),
+        Token::BraceOpen => delimiters.push(Delimiter::Block),
+        Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => {
+            delimiters.pop();
+        }
+        _ => (),
+    }
+}
+
+fn add_whitespace(t: &mut Tokonizer, token: &Token, config: &Config, inside: Option<&Delimiter>) {
+    // the padding inside a delimiter only depends on the option of that delimiter, not on what
+    // is around it `{ [1] }`. Empty delimiters `{}` and the ones broken over lines aren't padded
+    let opening = matches!(
+        token,
+        Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen
+    );
+    let closing_next = t.next_eq(Token::BraceClose)
+        | t.next_eq(Token::BraceSquareClosed)
+        | t.next_eq(Token::ParenClose);
+    if opening || closing_next {
+        let empty = opening && closing_next;
+        let broken =
+            token == &Token::NewLine || t.next_eq(Token::NewLine) || t.peak_next().is_none();
+        if inside.is_some_and(|e| e.padded(config)) && !empty && !broken {
+            t.to_stack(Token::WhiteSpace);
+        }
+        return;
+    }
+
+    // `;` ends the statement before it `let a = 1..3; ls`, the header of a table `[[a b]; [1 2]]`,
+    // and lines don't end in whitespace `{|e|`
+    if t.next_eq(Token::Semicolon) || t.next_eq(Token::NewLine) {
+        return;
+    }
+
+    match token {
+        // a caret on its own runs the subexpression after it `^($cmd)`
+        Token::External(e) if e == "^" => (),
+        Token::Word(w) | Token::External(w) => match t.peak_next_non_whitespace() {
+            Some(Token::Comma) | Some(Token::NewLine) | Some(Token::Colon) | Some(Token::MoreThen) | None => (),
+            // the types a type holds `list<string>`
+            Some(Token::LessThen) if GENERIC_TYPES.contains(&w.as_str()) => (),
+            Some(Token::LessThen) => match t.peak_prev_non_whitespace() {
+                Some(Token::Colon) => (),
+                _ => t.to_stack(Token::WhiteSpace),
+            },
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        // a flag ending in `=` is directly followed by its value `--name=[1 2]`
+        Token::Tag(f) | Token::TagLong(f) => match t.peak_next_non_whitespace() {
+            _ if f.ends_with('=') => (),
+            Some(Token::Comma) | Some(Token::NewLine) | Some(Token::Colon) | None => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        Token::Number(_)
+        | Token::Range(_)
+        | Token::Variable(_)
+        | Token::CellPath(_)
+        | Token::Path(_) => {
+            match t.peak_next_non_whitespace() {
+                Some(Token::Comma) | Some(Token::NewLine) => (),
+                _ => t.to_stack(Token::WhiteSpace),
+            }
+        }
+
+        Token::BraceClose | Token::BraceSquareClosed => match t.peak_next_non_whitespace() {
+            Some(Token::NewLine)
+            | Some(Token::Comma)
+            | Some(Token::CellPath(_))
+            | None => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+
+        Token::ParenClose => match t.peak_next_non_whitespace() {
+            Some(Token::Comma)
+            | Some(Token::Slash)
+            | Some(Token::CellPath(_))
+            | Some(Token::NewLine)
+            | None => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        Token::DoubleQuoteBlock(_) | Token::SingleQuoteBlock(_) => {
+            match t.peak_next_non_whitespace() {
+                // a quoted key of a record `{ "a b": 1 }`
+                Some(Token::Comma) | Some(Token::NewLine) | Some(Token::Colon) | None => (),
+                _ => t.to_stack(Token::WhiteSpace),
+            }
+        }
+        // the closing brackets of nested types `record<a: list<int>>`
+        Token::MoreThen => match t.peak_next_non_whitespace() {
+            Some(Token::Equals) | Some(Token::Tilda) | Some(Token::MoreThen) | Some(Token::Comma) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        Token::Equals | Token::Exc => match t.peak_next_non_whitespace() {
+            Some(Token::Equals) | Some(Token::Tilda) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+
+        Token::Operator(_) | Token::Redirect(_) | Token::EnvShorthand(_) | Token::FatArrow => {
+            match t.peak_next_non_whitespace() {
+                Some(Token::Comma) | Some(Token::NewLine) | None => (),
+                _ => t.to_stack(Token::WhiteSpace),
+            }
+        }
+        Token::Comma | Token::Semicolon => match t.peak_next_non_whitespace() {
+            Some(Token::NewLine) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        // the values of a NUON record are paths more often than urls `{ dir: /tmp }`
+        Token::Colon if config.nuon => match t.peak_next_non_whitespace() {
+            Some(Token::NewLine) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        Token::Colon | Token::Tilda => match t.peak_next_non_whitespace() {
+            Some(Token::Path(_)) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+        Token::LessThen => match t.peak_next_non_whitespace() {
+            Some(Token::Equals) | Some(Token::Word(_)) => (),
+            _ => t.to_stack(Token::WhiteSpace),
+        },
+
+        // dont add white space to these tokens, a dash left over is a unary minus `-$a`
+        Token::NewLine | Token::Dash | Token::Dolar | Token::Hash | Token::CommentBlock(_) | Token::Verbatim(_) | Token::Slash => (),
+        _ => t.to_stack(Token::WhiteSpace),
+    }
+}
+
+fn add_depth(token: &Token, depth: &mut usize) {
+    match token {
+        Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen => *depth += 1,
+        Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => {
+            *depth = depth.overflowing_sub(1).to_option().unwrap_or_default()
+        }
+        _ => (),
+    }
+}
+
+fn add_indent(t: &mut Tokonizer, token: &Token, depth: &mut usize) {
+    // blank lines are left empty and a region that isn't formatted keeps its own indentation
+    let verbatim = matches!(t.peak_next(), Some(Token::Verbatim(_)));
+    if token == &Token::NewLine && !t.next_eq(Token::NewLine) && !verbatim {
+        // add indent if next token is a Token::BraceClose it should substract one from depth
+        if t.next_eq(Token::BraceClose)
+            | t.next_eq(Token::BraceSquareClosed)
+            | t.next_eq(Token::ParenClose)
+        {
+            t.to_stack(Token::Tab(
+                depth.overflowing_sub(1).to_option().unwrap_or_default(),
+            ));
+        } else {
+            t.to_stack(Token::Tab(*depth));
+        }
+    }
+}
+
+impl Tokonizer {
+    // the formatted text, with the indentation and line endings written the way `config` asks
+    // for, and where every token that isn't layout ended up in it. Regions that aren't formatted
+    // are written exactly as they were
+    fn render(&self, config: &Config, newline: &str) -> (String, Vec<Range<usize>>) {
+        let end = self
+            .stack
+            .iter()
+            .rposition(|e| !matches!(e, Token::NewLine | Token::WhiteSpace | Token::Tab(_)))
+            .map_or(0, |e| e + 1);
+
+        let mut buffer = String::new();
+        let mut ranges = Vec::new();
+        for token in &self.stack[..end] {
+            let start = buffer.len();
+            match token {
+                Token::Tab(depth) => buffer.push_str(&config.indent(*depth)),
+                Token::Verbatim(text) => buffer.push_str(text),
+                _ => buffer.push_str(&token.as_string().replace('\n', newline)),
+            }
+            if !token.is_layout() {
+                ranges.push(start..buffer.len());
+            }
+        }
+
+        if !matches!(self.stack[..end].last(), Some(Token::Verbatim(_))) {
+            buffer.truncate(buffer.trim_end().len());
+        }
+        (buffer, ranges)
+    }
+}
+
+pub fn format_buffer(buffer: String) -> String {
+    format_buffer_with_config(buffer, &Config::default())
+}
+
+pub fn format_buffer_with_config(buffer: String, config: &Config) -> String {
+    format_buffer_at(&buffer, config, None).0
+}
+
+// Which token the byte `cursor` is in, or right after, or else the token after it, and how far
+// into that token it is. `spans` are in the order of the tokens after formatting
+fn cursor_token(spans: &[Span], cursor: usize) -> Option<(usize, usize)> {
+    let inside = spans
+        .iter()
+        .position(|e| e.start <= cursor && cursor < e.end)
+        .or_else(|| {
+            spans
+                .iter()
+                .position(|e| e.end == cursor && e.start < e.end)
+        });
+    if let Some(index) = inside {
+        return Some((index, cursor - spans[index].start));
+    }
+
+    let (index, _) = spans
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.start >= cursor)
+        .min_by_key(|(_, e)| e.start)?;
+    Some((index, 0))
+}
+
+// Formats `buffer` and follows its byte `cursor` to where it ends up in the result, through the
+// spans of the tokens. The cursor stays the same distance into the token it is in
+pub(crate) fn format_buffer_at(
+    buffer: &str,
+    config: &Config,
+    cursor: Option<usize>,
+) -> (String, Option<usize>) {
+    // `# nufmt: off` and `# nufmt: skip` regions are swapped for placeholders the lexer turns in
+    // tokens holding their text
+    let (protected, regions) = directives::protect(buffer);
+    let verbatim = regions
+        .iter()
+        .map(|e| &buffer[e.clone()])
+        .collect::<Vec<_>>();
+
+    let crlf = match config.line_ending {
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+        LineEnding::Auto => protected
+            .find('\n')
+            .is_some_and(|i| protected[..i].ends_with('\r')),
+    };
+    let final_newline = match config.final_newline {
+        FinalNewline::Always => true,
+        FinalNewline::Never => false,
+        FinalNewline::Preserve => protected.ends_with('\n'),
+    };
+
+    // the lexer only knows about `\n`, the line endings are put back at the end
+    let (tokens, spans) = gen_tokens(protected.replace("\r\n", "\n"), config, &verbatim);
+
+    // the spans are in the text the lexer saw, where every `\r\n` is a `\n` and every region
+    // is a placeholder
+    let returns = protected
+        .match_indices("\r\n")
+        .enumerate()
+        .map(|(i, (e, _))| e - i)
+        .collect::<Vec<_>>();
+    let mut shift = 0;
+    let placeholders = regions
+        .iter()
+        .map(|e| {
+            let placeholder = (e.start - shift, e.len() - 1);
+            shift += e.len() - 1;
+            placeholder
+        })
+        .collect::<Vec<_>>();
+    let source = |index: usize| {
+        let index = index + returns.partition_point(|e| *e < index);
+        let before = placeholders.partition_point(|(e, _)| *e < index);
+        index + placeholders[..before].iter().map(|(_, e)| e).sum::<usize>()
+    };
+    let anchor = cursor.map(|cursor| {
+        let spans = spans
+            .iter()
+            .map(|e| Span::new(source(e.start), source(e.end)))
+            .collect::<Vec<_>>();
+        cursor_token(&spans, cursor)
+    });
+
+    let mut depth: usize = 0;
+    let mut delimiters = Vec::new();
+    let mut t = Tokonizer::new(tokens);
+
+    while let Some(token) = t.get() {
+        t.to_stack(token.clone());
+        add_depth(&token, &mut depth);
+        add_delimiter(&t, &token, &mut delimiters);
+        add_whitespace(&mut t, &token, config, delimiters.last());
+        add_indent(&mut t, &token, &mut depth);
+        t.next();
+    }
+
+    if config.align_match_arms {
+        align_match_arms(&mut t.stack);
+    }
+
+    let newline = match crlf {
+        true => "\r\n",
+        false => "\n",
+    };
+    let (mut output, ranges) = t.render(config, newline);
+    if final_newline && !output.is_empty() {
+        output.push_str(newline);
+    }
+
+    // a cursor after the last token stays at the end
+    let cursor = anchor.map(|anchor| {
+        let Some((range, inside)) = anchor.and_then(|(i, inside)| Some((ranges.get(i)?, inside)))
+        else {
+            return output.len();
+        };
+        let mut index = (range.start + inside).min(range.end).min(output.len());
+        while !output.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    });
+    (output, cursor)
+}
+
+// Formats `buffer`, malformed input like an unterminated string or unbalanced delimiters is
+// rejected instead of formatted
+pub fn format_str(buffer: &str, config: &Config) -> Result<String, FormatError> {
+    validate::validate(buffer)?;
+
+    Ok(format_buffer_with_config(buffer.to_string(), config))
+}
+
+// Formats `buffer` like `format_str` and tells where its byte `cursor` ends up in the result
+pub fn format_with_cursor(
+    buffer: &str,
+    config: &Config,
+    cursor: Option<usize>,
+) -> Result<(String, Option<usize>), FormatError> {
+    validate::validate(buffer)?;
+
+    Ok(format_buffer_at(buffer, config, cursor))
+}
+
+// The tokens the formatter works on, after whitespace has been dropped and the layout rules
+// have run
+pub fn tokenize(buffer: &str, config: &Config) -> Vec<Token> {
+    tokenize_with_spans(buffer, config).0
+}
+
+// `tokenize` with where in `buffer` the tokens that aren't layout come from
+pub(crate) fn tokenize_with_spans(buffer: &str, config: &Config) -> (Vec<Token>, Vec<Span>) {
+    let (tokens, spans) = gen_tokens(buffer.replace("\r\n", "\n"), config, &[]);
+    let returns = buffer
+        .match_indices("\r\n")
+        .enumerate()
+        .map(|(i, (e, _))| e - i)
+        .collect::<Vec<_>>();
+    let source = |index: usize| index + returns.partition_point(|e| *e < index);
+    let spans = spans
+        .into_iter()
+        .map(|e| Span::new(source(e.start), source(e.end)))
+        .collect();
+    (tokens, spans)
+}
@@ -0,0 +1,349 @@
+use crate::{
+    config::{Config, ListSeparator},
+    tokens::Token,
+};
+
+// Lists are rewritten before the whitespace is filtered out of the token stream, in a list the
+// whitespace is what separates the items: `[1 2 3]`
+pub fn normalise_lists(tokens: Vec<Token>, config: &Config) -> Vec<Token> {
+    let mut out = Vec::with_capacity(tokens.len());
+    normalise_into(&tokens, 0, 0, config, &mut out);
+    out
+}
+
+// `column` is where the first token of `tokens` ends up on its line and `depth` the number of
+// delimiters around it, the lines after the first are indented that deep
+fn normalise_into(
+    tokens: &[Token],
+    column: usize,
+    depth: usize,
+    config: &Config,
+    out: &mut Vec<Token>,
+) {
+    let start = out.len();
+    let mut index = 0;
+
+    while let Some(token) = tokens.get(index) {
+        if token == &Token::BraceSquareOpen && !is_signature(out) {
+            if let Some(end) = matching_close(tokens, index) {
+                let written = &out[start..];
+                let column = match last_line_depth(written, depth) {
+                    Some(depth) => indent_width(config, depth) + line_width(written),
+                    None => column + line_width(written),
+                };
+                let depth = depth + delimiter_depth(written);
+
+                match rebuild_list(&tokens[index + 1..end], column, depth, config) {
+                    Some(list) => out.extend(list),
+                    None => {
+                        out.push(Token::BraceSquareOpen);
+                        normalise_into(&tokens[index + 1..end], column + 1, depth + 1, config, out);
+                        out.push(Token::BraceSquareClosed);
+                    }
+                }
+
+                index = end + 1;
+                continue;
+            }
+        }
+
+        out.push(token.clone());
+        index += 1;
+    }
+}
+
+fn is_opener(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen
+    )
+}
+
+fn closes(open: &Token, close: &Token) -> bool {
+    matches!(
+        (open, close),
+        (Token::BraceOpen, Token::BraceClose)
+            | (Token::BraceSquareOpen, Token::BraceSquareClosed)
+            | (Token::ParenOpen, Token::ParenClose)
+    )
+}
+
+fn is_closer(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose
+    )
+}
+
+// Index of the bracket closing the one at `open`, None when the brackets in between don't match
+fn matching_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut stack: Vec<&Token> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        if is_opener(token) {
+            stack.push(token);
+        } else if is_closer(token) {
+            if !closes(stack.pop()?, token) {
+                return None;
+            }
+            if stack.is_empty() {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+// The parameter list of `def name [x: int, y: string]` looks like a list but its items contain
+// whitespace, walking back to the start of the statement tells them apart
+fn is_signature(before: &[Token]) -> bool {
+    for token in before.iter().rev() {
+        match token {
+            Token::Word(w) if matches!(w.as_str(), "def" | "def-env" | "extern") => return true,
+            Token::NewLine
+            | Token::Pipe
+            | Token::BraceOpen
+            | Token::ParenOpen
+            | Token::BraceSquareOpen
+            | Token::BraceSquareClosed => return false,
+            _ => (),
+        }
+    }
+    false
+}
+
+// number of delimiters `tokens` leaves open
+fn delimiter_depth(tokens: &[Token]) -> usize {
+    tokens.iter().fold(0, |depth: usize, e| match e {
+        _ if is_opener(e) => depth + 1,
+        _ if is_closer(e) => depth.saturating_sub(1),
+        _ => depth,
+    })
+}
+
+// How deep the last line of `tokens` is indented when they start `depth` delimiters deep, None
+// when they are on a single line. A line starting with a closing delimiter is one level less
+fn last_line_depth(tokens: &[Token], depth: usize) -> Option<usize> {
+    let newline = tokens.iter().rposition(|e| e == &Token::NewLine)?;
+    let depth = depth + delimiter_depth(&tokens[..newline]);
+    match tokens[newline + 1..].iter().find(|e| !is_separator(e)) {
+        Some(e) if is_closer(e) => Some(depth.saturating_sub(1)),
+        _ => Some(depth),
+    }
+}
+
+fn indent_width(config: &Config, depth: usize) -> usize {
+    config.indent(depth).chars().count()
+}
+
+// Width of the text on the current line, runs of whitespace count as one
+fn line_width(before: &[Token]) -> usize {
+    let line = before
+        .iter()
+        .rev()
+        .take_while(|e| **e != Token::NewLine)
+        .collect::<Vec<_>>();
+
+    let mut width = 0;
+    let mut prev_white = true;
+    for token in line.into_iter().rev() {
+        let white = matches!(token, Token::WhiteSpace | Token::Tab(_));
+        if !(white && prev_white) {
+            width += token.as_string().chars().count();
+        }
+        prev_white = white;
+    }
+    width
+}
+
+fn is_separator(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::WhiteSpace | Token::Tab(_) | Token::NewLine | Token::Comma
+    )
+}
+
+#[derive(Default)]
+struct Gap {
+    comma: bool,
+    newline: bool,
+}
+
+// Splits the inside of a list in its items and the separators between them, the first gap is
+// the one after the opening bracket and the last the one before the closing bracket
+fn split_items(inner: &[Token]) -> (Vec<Vec<Token>>, Vec<Gap>) {
+    let mut items: Vec<Vec<Token>> = Vec::new();
+    let mut gaps = vec![Gap::default()];
+    let mut item = Vec::new();
+    let mut depth = 0;
+
+    for token in inner {
+        if depth == 0 && is_separator(token) {
+            if !item.is_empty() {
+                items.push(std::mem::take(&mut item));
+                gaps.push(Gap::default());
+            }
+            let gap = gaps.last_mut().unwrap();
+            gap.comma |= token == &Token::Comma;
+            gap.newline |= token == &Token::NewLine;
+            continue;
+        }
+
+        if is_opener(token) {
+            depth += 1;
+        } else if is_closer(token) {
+            depth -= 1;
+        }
+        item.push(token.clone());
+    }
+
+    if !item.is_empty() {
+        items.push(item);
+        gaps.push(Gap::default());
+    }
+    (items, gaps)
+}
+
+// the `;` between the columns and rows of a table `[[a b]; [1 2]]`
+fn table_separator(inner: &[Token]) -> Option<usize> {
+    let mut depth = 0;
+    inner.iter().position(|e| {
+        if is_opener(e) {
+            depth += 1;
+        } else if is_closer(e) {
+            depth -= 1;
+        }
+        depth == 0 && e == &Token::Semicolon
+    })
+}
+
+// The columns of a table are left as they are, its rows are separated like the items of a list
+fn rebuild_table(
+    inner: &[Token],
+    semicolon: usize,
+    column: usize,
+    depth: usize,
+    config: &Config,
+) -> Option<Vec<Token>> {
+    let mut table = vec![Token::BraceSquareOpen];
+    normalise_into(
+        &inner[..semicolon],
+        column + 1,
+        depth + 1,
+        config,
+        &mut table,
+    );
+    table.push(Token::Semicolon);
+
+    let rows = &inner[semicolon + 1..];
+    let column = match last_line_depth(&table, depth) {
+        Some(depth) => indent_width(config, depth) + line_width(&table),
+        None => column + line_width(&table),
+    };
+    let rows = rebuild_list(rows, column, depth, config)?;
+    table.extend_from_slice(&rows[1..rows.len() - 1]);
+    table.push(Token::BraceSquareClosed);
+    Some(table)
+}
+
+fn rebuild_list(
+    inner: &[Token],
+    column: usize,
+    depth: usize,
+    config: &Config,
+) -> Option<Vec<Token>> {
+    // comments, regions that aren't formatted and a table with more than one `;` are left as
+    // they are
+    let untouchable = inner
+        .iter()
+        .any(|e| matches!(e, Token::CommentBlock(_) | Token::Verbatim(_)));
+    if untouchable {
+        return None;
+    }
+    if let Some(semicolon) = table_separator(inner) {
+        return match inner[semicolon + 1..].contains(&Token::Semicolon) {
+            true => None,
+            false => rebuild_table(inner, semicolon, column, depth, config),
+        };
+    }
+    if inner.contains(&Token::Semicolon) {
+        return None;
+    }
+
+    let (items, gaps) = split_items(inner);
+    if items.is_empty() {
+        return Some(vec![Token::BraceSquareOpen, Token::BraceSquareClosed]);
+    }
+
+    let comma = |gap: &Gap| match config.list_separator {
+        ListSeparator::Commas => true,
+        ListSeparator::Spaces => false,
+        ListSeparator::Preserve => gap.comma,
+    };
+
+    let multiline =
+        gaps.iter().any(|e| e.newline) || items.iter().flatten().any(|e| e == &Token::NewLine);
+    let separator_width = match config.list_separator {
+        ListSeparator::Spaces => 1,
+        _ => 2,
+    };
+    let width = column
+        + 2
+        + if config.square_padding { 2 } else { 0 }
+        + items.iter().map(|e| line_width(e)).sum::<usize>()
+        + (items.len() - 1) * separator_width;
+
+    // lists that already span lines keep their line breaks, a list that doesn't fit is broken
+    // one item per line
+    let broken = !multiline && width > config.max_width;
+    let newline_at = |index: usize| broken || gaps[index].newline;
+
+    let mut list = vec![Token::BraceSquareOpen];
+    if newline_at(0) {
+        list.push(Token::NewLine);
+    }
+
+    let last = items.len();
+    for (index, item) in items.iter().enumerate() {
+        // nested lists are measured from where the item starts
+        let item_column = match last_line_depth(&list, depth) {
+            Some(depth) => indent_width(config, depth) + line_width(&list),
+            None => column + line_width(&list),
+        };
+        normalise_into(item, item_column, depth + 1, config, &mut list);
+
+        let gap = index + 1;
+        if gap == last {
+            let closes_on_own_line = newline_at(gap);
+            // items separated by spaces don't get a comma after the last one
+            let trailing = closes_on_own_line
+                && match config.list_separator {
+                    ListSeparator::Commas => config.trailing_comma,
+                    ListSeparator::Spaces => false,
+                    ListSeparator::Preserve => {
+                        gaps[gap].comma
+                            || (config.trailing_comma && gaps[1..gap].iter().any(|e| e.comma))
+                    }
+                };
+            if trailing {
+                list.push(Token::Comma);
+            }
+            if closes_on_own_line {
+                list.push(Token::NewLine);
+            }
+            continue;
+        }
+
+        if comma(&gaps[gap]) {
+            list.push(Token::Comma);
+        }
+        if newline_at(gap) {
+            list.push(Token::NewLine);
+        } else {
+            list.push(Token::WhiteSpace);
+        }
+    }
+
+    list.push(Token::BraceSquareClosed);
+    Some(list)
+}
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait,
+    },
+    request::{Formatting, OnTypeFormatting, RangeFormatting, Request as RequestTrait},
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams,
+    DocumentRangeFormattingParams, FormattingOptions, OneOf, Position, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+
+use crate::{
+    config::{Config, IndentStyle},
+    diff::{diff, position, Edit},
+    format_range, format_str,
+};
+
+type LspResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+fn lsp_position(text: &str, offset: usize) -> Position {
+    let (line, character) = position(text, offset);
+    Position::new(line as u32, character as u32)
+}
+
+// Byte offset of an LSP position, positions past the end of a line are clamped to it
+fn offset(text: &str, position: Position) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(|e| e.len())
+        .sum::<usize>();
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+// The indentation the editor asks for, a tab or `tab_size` spaces per level
+fn with_options(config: &Config, options: &FormattingOptions) -> Config {
+    let (indent_style, indent_width) = match options.insert_spaces {
+        true => (IndentStyle::Spaces, options.tab_size as usize),
+        false => (IndentStyle::Tabs, 1),
+    };
+    Config {
+        indent_style,
+        indent_width,
+        ..config.clone()
+    }
+}
+
+fn text_edit(text: &str, edit: Edit) -> TextEdit {
+    TextEdit::new(
+        Range::new(lsp_position(text, edit.start), lsp_position(text, edit.end)),
+        edit.text,
+    )
+}
+
+// Edits formatting `text`, when `range` is given only the statements overlapping those bytes
+fn format_edits(
+    text: &str,
+    range: Option<(usize, usize)>,
+    config: &Config,
+) -> Result<Vec<TextEdit>, String> {
+    let formatted = match range {
+        Some((start, end)) => format_range(text, start..end, config),
+        None => format_str(text, config),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(diff(text, &formatted)
+        .into_iter()
+        .map(|e| text_edit(text, e))
+        .collect())
+}
+
+// The lines to format after typing `ch` at `offset`, the block closed by a `}` or the line
+// finished by a newline
+fn on_type_range(text: &str, offset: usize, ch: &str) -> Option<(usize, usize)> {
+    let line_start = |i: usize| text[..i].rfind('\n').map(|e| e + 1).unwrap_or_default();
+
+    match ch {
+        "}" => {
+            let close = text[..offset].rfind('}')?;
+            let mut depth = 0;
+            for (index, c) in text[..close + 1].char_indices().rev() {
+                match c {
+                    '}' => depth += 1,
+                    '{' => depth -= 1,
+                    _ => (),
+                }
+                if depth == 0 {
+                    return Some((line_start(index), close + 1));
+                }
+            }
+            None
+        }
+        "\n" => {
+            let end = line_start(offset).checked_sub(1)?;
+            Some((line_start(end), end))
+        }
+        _ => None,
+    }
+}
+
+fn handle_request(documents: &HashMap<Url, String>, request: Request, config: &Config) -> Response {
+    let id = request.id.clone();
+
+    let result = match request.method.as_str() {
+        Formatting::METHOD => serde_json::from_value::<DocumentFormattingParams>(request.params)
+            .map_err(|e| e.to_string())
+            .and_then(|params| {
+                let text = documents
+                    .get(&params.text_document.uri)
+                    .ok_or("unknown document")?;
+                format_edits(text, None, &with_options(config, &params.options))
+            }),
+        RangeFormatting::METHOD => {
+            serde_json::from_value::<DocumentRangeFormattingParams>(request.params)
+                .map_err(|e| e.to_string())
+                .and_then(|params| {
+                    let text = documents
+                        .get(&params.text_document.uri)
+                        .ok_or("unknown document")?;
+                    let range = (
+                        offset(text, params.range.start),
+                        offset(text, params.range.end),
+                    );
+                    format_edits(text, Some(range), &with_options(config, &params.options))
+                })
+        }
+        OnTypeFormatting::METHOD => {
+            serde_json::from_value::<DocumentOnTypeFormattingParams>(request.params)
+                .map_err(|e| e.to_string())
+                .map(|params| {
+                    let document = params.text_document_position;
+                    let Some(text) = documents.get(&document.text_document.uri) else {
+                        return Vec::new();
+                    };
+                    let cursor = offset(text, document.position);
+                    let config = with_options(config, &params.options);
+                    // half typed code doesn't format, which is no reason to bother the user
+                    on_type_range(text, cursor, &params.ch)
+                        .and_then(|range| format_edits(text, Some(range), &config).ok())
+                        .unwrap_or_default()
+                })
+        }
+        method => {
+            return Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", method),
+            )
+        }
+    };
+
+    match result {
+        Ok(edits) => Response::new_ok(id, edits),
+        Err(err) => Response::new_err(id, ErrorCode::RequestFailed as i32, err),
+    }
+}
+
+fn handle_notification(documents: &mut HashMap<Url, String>, notification: Notification) {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            if let Ok(params) =
+                serde_json::from_value::<DidOpenTextDocumentParams>(notification.params)
+            {
+                documents.insert(params.text_document.uri, params.text_document.text);
+            }
+        }
+        // the server asks for full sync so the last change is the whole document
+        DidChangeTextDocument::METHOD => {
+            if let Ok(params) =
+                serde_json::from_value::<DidChangeTextDocumentParams>(notification.params)
+            {
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    documents.insert(params.text_document.uri, change.text);
+                }
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            if let Ok(params) =
+                serde_json::from_value::<DidCloseTextDocumentParams>(notification.params)
+            {
+                documents.remove(&params.text_document.uri);
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".to_string(),
+            more_trigger_character: Some(vec!["\n".to_string()]),
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+// Runs the language server on `connection` until the client shuts it down
+pub fn serve(connection: Connection, config: &Config) -> LspResult<()> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&documents, request, config);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                handle_notification(&mut documents, notification)
+            }
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
+
+// The language server over stdin and stdout, `nushell_fmt lsp`
+pub fn serve_stdio(config: &Config) -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection, config)?;
+    io_threads.join()?;
+
+    Ok(())
+}
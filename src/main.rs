@@ -1,269 +1,254 @@
-#[cfg(test)]
-mod test;
+use std::{fs::read_to_string, io::Write, path::Path, process::ExitCode};
 
-#[allow(unused)]
-mod tokenizer;
-mod tokens;
-
-use std::{fs::read_to_string, io::Write};
-
-use clap::{arg, Parser};
-use tokenizer::Tokonizer;
-use tokens::{Token, TokonizerTools};
+use clap::{Parser, Subcommand, ValueEnum};
+use nushell_fmt::{
+    diff::diff,
+    format_lines_with_cursor, format_range_with_cursor, format_with_cursor, git, line_range, lsp,
+    markdown::format_markdown,
+    nuon::{format_nuon, format_nuon_with_cursor},
+    Config, FormatError,
+};
 
 #[derive(Parser, Debug)]
-#[command(version,about,long_about = None)]
+#[command(version,about,long_about = None, subcommand_negates_reqs = true)]
 struct AppArgs {
-    #[arg(short, long)]
-    filename: String,
-}
+    #[command(subcommand)]
+    command: Option<Command>,
 
-fn gen_tokens(buffer: String) -> Vec<Token> {
-    let tokens = buffer.chars().map(Token::from).collect::<Vec<_>>();
-    let mut t = Tokonizer::new(tokens);
-
-    while let Some(token) = t.get() {
-        match token {
-            Token::Char(_) => t.to_temp(token.as_string()),
-            Token::WhiteSpace
-            | Token::NewLine
-            | Token::Pipe
-            | Token::Comma
-            | Token::Equals
-            | Token::ParenOpen
-            | Token::MoreThen
-            | Token::LessThen
-            | Token::Exc
-            | Token::ParenClose
-            | Token::BraceOpen
-            | Token::BraceClose
-            | Token::BraceSquareOpen
-            | Token::BraceSquareClosed
-            | Token::Tilda
-            | Token::Colon => {
-                // move word to stack
-                t.temp_to_word_or_number();
-
-                // push current to stack
-                t.to_stack(token);
-            }
-            Token::DoubleQuote => {
-                // take tokens upto DoubleQuote and add them to the stack
-                let block = t.take_upto(&[Token::DoubleQuote], true);
+    #[arg(short, long, required = true)]
+    filename: Option<String>,
 
-                t.to_stack(Token::DoubleQuoteBlock(block));
-            }
-            Token::SingleQuote => {
-                // take tokens upto SingleQuote and add them to the stack
-                let block = t.take_upto(&[Token::SingleQuote], true);
+    /// What the file holds, picked from its extension by default
+    #[arg(long, value_enum, default_value_t = Language::Auto)]
+    language: Language,
 
-                t.to_stack(Token::SingleQuoteBlock(block));
-            }
-            // if a slash is found it means there is a path.. taking the path untill next white
-            // space or brace close or paren close
-            Token::Slash => {
-                let block = t
-                    .take_upto(&[Token::WhiteSpace, Token::BraceClose], true)
-                    .trim_end()
-                    .to_string();
-
-                t.to_stack(Token::Path(format!("{}{}", t.temp, block)));
-                t.temp.clear();
-            }
-            Token::Att=> {
-                let block = t
-                    .take_upto(&[Token::NewLine], false)
-                    .trim_end()
-                    .to_string();
-
-                t.to_stack(Token::AttSomething(format!("{}{}", t.temp, block)));
-                t.temp.clear();
-            }
+    /// Don't print anything, exit with an error when the file isn't formatted
+    #[arg(long, conflicts_with_all = ["output", "cursor_offset"])]
+    check: bool,
 
-            // Commant block
-            Token::Hash => {
-                // If line starts with a hash its a commant block.. walking upto new line and but
-                // the line in a commant_block token. formatting is ignored on this line, commant
-                // indent is still applied
-                let block = t.take_upto(&[Token::NewLine], false);
+    /// Write a NUON file on a single line
+    #[arg(long)]
+    compact: bool,
 
-                t.to_stack(Token::CommentBlock(block));
-            }
+    /// Only format the statements on the lines `start:end`, counted from 1
+    #[arg(long, value_parser = parse_line_range, conflicts_with = "byte_range")]
+    range: Option<(usize, usize)>,
 
-            _ => {
-                // push current to stack
-                t.to_stack(token);
-            }
-        }
-        t.next();
-    }
+    /// Only format the statements overlapping the bytes `start:end`
+    #[arg(long, value_parser = parse_range)]
+    byte_range: Option<(usize, usize)>,
 
-    t.temp_to_word_or_number();
-    t.stack
-        .into_iter()
-        .filter(|e| e != &Token::WhiteSpace && e != &Token::Tab(0))
-        .collect::<Vec<_>>()
+    /// Only format the lines changed in git since the last commit
+    #[arg(long, conflicts_with_all = ["range", "byte_range"])]
+    changed: bool,
+
+    /// Only format the lines changed in git since this revision
+    #[arg(long, value_name = "REV", conflicts_with_all = ["range", "byte_range", "changed"])]
+    since: Option<String>,
+
+    /// Write the formatted file, or a JSON list of the edits turning the file into it
+    #[arg(long, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
+    /// Print JSON with the formatted file and where this byte offset of the file ended up in it
+    #[arg(long, value_name = "N", conflicts_with = "output")]
+    cursor_offset: Option<usize>,
+
+    #[command(flatten)]
+    config: Config,
 }
 
-fn add_whitespace(t: &mut Tokonizer, token: &Token) {
-    match token {
-        Token::Word(_) => match t.peak_next_non_whitespace() {
-            Some(Token::Comma) | Some(Token::NewLine) | Some(Token::Colon) | Some(Token::MoreThen) | None => (),
-            Some(Token::LessThen) => match t.peak_prev_non_whitespace() {
-                Some(Token::Colon) => (),
-                _ => t.to_stack(Token::WhiteSpace),
-            },
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-        Token::Number(_) => match t.peak_next_non_whitespace() {
-            Some(Token::NewLine) => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        }
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Auto,
+    Nu,
+    /// Format the ```nu and ```nushell blocks of a Markdown file
+    Markdown,
+    /// A data file that holds a single value without any commands
+    Nuon,
+}
 
-        Token::BraceOpen => match t.peak_next_non_whitespace() {
-            Some(Token::BraceOpen)
-            | Some(Token::BraceSquareOpen)
-            | Some(Token::ParenOpen)
-            | Some(Token::BraceClose)
-            | Some(Token::NewLine)
-            | None => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        Token::BraceClose | Token::BraceSquareClosed => match t.peak_next_non_whitespace() {
-            Some(Token::BraceClose)
-            | Some(Token::BraceSquareClosed)
-            | Some(Token::ParenClose)
-            | Some(Token::NewLine)
-            | Some(Token::Comma)
-            | None => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        Token::BraceSquareOpen => match t.peak_next_non_whitespace() {
-            Some(Token::BraceOpen)
-            | Some(Token::BraceSquareOpen)
-            | Some(Token::ParenOpen)
-            | Some(Token::BraceSquareClosed)
-            | Some(Token::NewLine)
-            | None => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        Token::ParenOpen => match t.peak_next_non_whitespace() {
-            Some(Token::BraceOpen)
-            | Some(Token::BraceSquareOpen)
-            | Some(Token::ParenClose)
-            | Some(Token::NewLine)
-            | None => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        Token::ParenClose => match t.peak_next_non_whitespace() {
-            Some(Token::BraceClose)
-            | Some(Token::BraceSquareClosed)
-            | Some(Token::ParenClose)
-            | Some(Token::Comma)
-            | Some(Token::Slash)
-            | Some(Token::NewLine)
-            | None => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-        Token::DoubleQuoteBlock(_) | Token::SingleQuoteBlock(_) => {
-            match t.peak_next_non_whitespace() {
-                Some(Token::NewLine) | None => (),
-                _ => t.to_stack(Token::WhiteSpace),
-            }
+impl Language {
+    fn of(self, filename: &str) -> Language {
+        if self != Language::Auto {
+            return self;
+        }
+        match Path::new(filename).extension().and_then(|e| e.to_str()) {
+            Some("md" | "markdown") => Language::Markdown,
+            Some("nuon") => Language::Nuon,
+            _ => Language::Nu,
         }
-        Token::Equals | Token::MoreThen | Token::Exc => match t.peak_next_non_whitespace() {
-            Some(Token::Equals) | Some(Token::Tilda) => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        Token::Comma => match t.peak_next_non_whitespace() {
-            Some(Token::NewLine) => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-        Token::Colon | Token::Tilda => match t.peak_next_non_whitespace() {
-            Some(Token::Path(_)) => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-        Token::LessThen => match t.peak_next_non_whitespace() {
-            Some(Token::Equals) | Some(Token::Word(_)) => (),
-            _ => t.to_stack(Token::WhiteSpace),
-        },
-
-        // dont add white space to these tokens
-        Token::NewLine | Token::Dolar | Token::Hash | Token::CommentBlock(_) | Token::Slash => (),
-        _ => t.to_stack(Token::WhiteSpace),
     }
 }
 
-fn add_depth(token: &Token, depth: &mut usize) {
-    match token {
-        Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen => *depth += 1,
-        Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => {
-            *depth = depth.overflowing_sub(1).to_option().unwrap_or_default()
-        }
-        _ => (),
-    }
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Text,
+    Edits,
 }
 
-fn add_indent(t: &mut Tokonizer, token: &Token, depth: &mut usize) {
-    match token {
-        Token::NewLine => {
-            // add indent if next token is a Token::BraceClose it should substract one from depth
-            if t.next_eq(Token::BraceClose)
-                | t.next_eq(Token::BraceSquareClosed)
-                | t.next_eq(Token::ParenClose)
-            {
-                t.to_stack(Token::Tab(
-                    depth.overflowing_sub(1).to_option().unwrap_or_default(),
-                ));
-            } else {
-                t.to_stack(Token::Tab(depth.clone()));
-            }
-        }
-        _ => (),
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a language server over stdin and stdout, the layout flags go before `lsp`
+    Lsp,
+}
+
+// `3:10` into (3, 10)
+fn parse_range(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected start:end, got `{}`", value))?;
+    let start = start.parse::<usize>().map_err(|e| e.to_string())?;
+    let end = end.parse::<usize>().map_err(|e| e.to_string())?;
+    if end < start {
+        return Err(format!("the range {} ends before it starts", value));
     }
+    Ok((start, end))
 }
 
-impl ToString for Tokonizer {
-    fn to_string(&self) -> String {
-        self.stack
-            .iter()
-            .map(|e| e.as_string())
-            .collect::<String>()
-            .trim_end()
-            .to_string()
+// `3:10` into (3, 10), lines are counted from 1
+fn parse_line_range(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = parse_range(value)?;
+    if start == 0 {
+        return Err(format!(
+            "the range {} starts at line 0, lines are counted from 1",
+            value
+        ));
     }
+    Ok((start, end))
 }
 
-pub fn format_buffer(buffer: String) -> String {
-    let mut depth: usize = 0;
-    let mut t = Tokonizer::new(gen_tokens(buffer));
+// error: unbalanced delimiter, `(` closed by `]`
+//  --> script.nu:1:4
+//   |
+// 1 | ls (a]
+//   |    ^ `(` opened here
+fn render_error(err: &FormatError, filename: &str, source: &str) -> String {
+    let labels = err.labels();
+    if labels.is_empty() {
+        return format!("error: {}: {}\n", filename, err);
+    }
+
+    let mut out = format!("error: {}\n", err);
+
+    for (span, label) in labels {
+        let (line, col) = span.line_col(source);
+        let text = source.lines().nth(line - 1).unwrap_or_default();
+        let width = line.to_string().len();
 
-    while let Some(token) = t.get() {
-        t.to_stack(token.clone());
-        add_depth(&token, &mut depth);
-        add_whitespace(&mut t, &token);
-        add_indent(&mut t, &token, &mut depth);
-        t.next();
+        out.push_str(&format!("{:width$}--> {}:{}:{}\n", "", filename, line, col));
+        out.push_str(&format!("{:width$} |\n", ""));
+        out.push_str(&format!("{} | {}\n", line, text));
+        out.push_str(&format!("{:width$} | {:>col$} {}\n", "", "^", label));
     }
+    out
+}
 
-    t.to_string()
+fn report(err: FormatError, filename: &str, source: &str) -> ExitCode {
+    eprint!("{}", render_error(&err, filename, source));
+    ExitCode::FAILURE
 }
 
-fn main() -> Result<(), std::io::Error> {
+fn main() -> ExitCode {
     let args = AppArgs::parse();
 
-    let filename = args.filename;
-    let buffer = read_to_string(&filename)?;
+    if let Some(Command::Lsp) = args.command {
+        return match lsp::serve_stdio(&args.config) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        };
+    }
 
-    let new_buffer = format_buffer(buffer);
+    // clap only lets a missing filename through with a subcommand
+    let filename = args.filename.unwrap_or_default();
+    let buffer = match read_to_string(&filename) {
+        Ok(buffer) => buffer,
+        Err(err) => return report(err.into(), &filename, ""),
+    };
+
+    let since = args.since.or(args.changed.then(|| "HEAD".to_string()));
+    let language = args.language.of(&filename);
+    if language != Language::Nu
+        && (args.range.is_some() || args.byte_range.is_some() || since.is_some())
+    {
+        eprintln!("error: only whole Markdown and NUON files can be formatted");
+        return ExitCode::FAILURE;
+    }
+    if args.compact && language != Language::Nuon {
+        eprintln!("error: --compact only applies to NUON files");
+        return ExitCode::FAILURE;
+    }
+    if args.cursor_offset.is_some() && (language == Language::Markdown || args.compact) {
+        eprintln!("error: --cursor-offset doesn't apply to Markdown files and compact NUON");
+        return ExitCode::FAILURE;
+    }
+
+    let cursor = args.cursor_offset;
+    let formatted = match (args.range, args.byte_range, since) {
+        _ if language == Language::Nuon && args.compact => {
+            format_nuon(&buffer, &args.config, true).map(|e| (e, None))
+        }
+        _ if language == Language::Nuon => format_nuon_with_cursor(&buffer, &args.config, cursor),
+        _ if language == Language::Markdown => {
+            let (formatted, blocks) = format_markdown(&buffer, &args.config);
+            for block in &blocks {
+                if let Some(err) = &block.error {
+                    eprintln!(
+                        "warning: {}:{}: left the block as it is, {}",
+                        filename, block.line, err
+                    );
+                } else if args.check && block.changed {
+                    eprintln!("{}:{}: the block isn't formatted", filename, block.line);
+                }
+            }
+            Ok((formatted, None))
+        }
+        (Some((start, end)), _, _) => line_range(&buffer, start, end)
+            .and_then(|range| format_range_with_cursor(&buffer, range, &args.config, cursor)),
+        (_, Some((start, end)), _) => {
+            format_range_with_cursor(&buffer, start..end, &args.config, cursor)
+        }
+        (_, _, Some(rev)) => git::changed_lines(Path::new(&filename), &rev)
+            .and_then(|lines| format_lines_with_cursor(&buffer, &lines, &args.config, cursor)),
+        _ => format_with_cursor(&buffer, &args.config, cursor),
+    };
+    let (new_buffer, cursor) = match formatted {
+        Ok(new_buffer) => new_buffer,
+        Err(err) => return report(err, &filename, &buffer),
+    };
+
+    if args.check {
+        if new_buffer == buffer {
+            return ExitCode::SUCCESS;
+        }
+        if language != Language::Markdown {
+            eprintln!("{}: the file isn't formatted", filename);
+        }
+        return ExitCode::FAILURE;
+    }
+
+    let output = match (args.output, cursor) {
+        (_, Some(cursor)) => {
+            let result = serde_json::json!({ "formatted": new_buffer, "cursor": cursor });
+            format!("{}\n", result)
+        }
+        (Output::Text, None) => new_buffer,
+        (Output::Edits, None) => {
+            let edits = diff(&buffer, &new_buffer)
+                .iter()
+                .map(|e| e.to_json(&buffer))
+                .collect::<Vec<_>>();
+            format!("{}\n", serde_json::Value::Array(edits))
+        }
+    };
 
     let mut lock = std::io::stdout().lock();
-    lock.write_all(new_buffer.as_bytes())?;
+    if let Err(err) = lock.write_all(output.as_bytes()) {
+        return report(err.into(), &filename, &buffer);
+    }
 
-    Ok(())
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,122 @@
+use crate::{
+    config::{Config, FinalNewline},
+    error::FormatError,
+    format_str,
+};
+
+// A Nushell block found in a Markdown file
+#[derive(Debug)]
+pub struct CodeBlock {
+    // line of the opening fence, counted from 1
+    pub line: usize,
+    pub changed: bool,
+    // why the block was left as it is
+    pub error: Option<FormatError>,
+}
+
+// ```nu or ~~~nushell, the indentation, the fence and whether it's a Nushell block
+fn open_fence(line: &str) -> Option<(&str, &str, bool)> {
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+    let marker = content.chars().next().filter(|e| *e == '`' || *e == '~')?;
+    let length = content.len() - content.trim_start_matches(marker).len();
+    if length < 3 {
+        return None;
+    }
+
+    let fence = &content[..length];
+    let language = content[length..]
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    let nushell = ["nu", "nushell"].contains(&language.to_lowercase().as_str());
+
+    Some((indent, fence, nushell))
+}
+
+fn is_close_fence(line: &str, fence: &str) -> bool {
+    let content = line.trim();
+    let marker = fence.chars().next().unwrap_or('`');
+
+    content.len() >= fence.len() && content.chars().all(|e| e == marker)
+}
+
+// removes up to the width of the fence indentation from the start of `line`
+fn dedent<'a>(line: &'a str, indent: &str) -> &'a str {
+    let width = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[width.min(indent.len())..]
+}
+
+fn format_block(code: &str, indent: &str, config: &Config) -> Result<String, FormatError> {
+    let code = code
+        .split_inclusive('\n')
+        .map(|e| dedent(e, indent))
+        .collect::<String>();
+    if code.trim().is_empty() {
+        return Ok(code);
+    }
+
+    let config = Config {
+        final_newline: FinalNewline::Always,
+        ..config.clone()
+    };
+    let formatted = format_str(&code, &config)?;
+
+    Ok(formatted
+        .split_inclusive('\n')
+        .map(|e| match e.trim().is_empty() {
+            true => e.to_string(),
+            false => format!("{}{}", indent, e),
+        })
+        .collect())
+}
+
+// Formats the ```nu and ```nushell fenced blocks of a Markdown file and leaves everything else
+// as it is. Blocks that don't parse are left alone and returned with their error
+pub fn format_markdown(buffer: &str, config: &Config) -> (String, Vec<CodeBlock>) {
+    let lines = buffer.split_inclusive('\n').collect::<Vec<_>>();
+
+    let mut out = String::with_capacity(buffer.len());
+    let mut blocks = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        out.push_str(lines[index]);
+        let Some((indent, fence, nushell)) = open_fence(lines[index]) else {
+            index += 1;
+            continue;
+        };
+
+        // an unclosed fence runs to the end of the file
+        let close = (index + 1..lines.len())
+            .find(|e| is_close_fence(lines[*e], fence))
+            .unwrap_or(lines.len());
+        let code = lines[index + 1..close].concat();
+
+        if nushell {
+            let mut block = CodeBlock {
+                line: index + 1,
+                changed: false,
+                error: None,
+            };
+            match format_block(&code, indent, config) {
+                Ok(formatted) => {
+                    block.changed = formatted != code;
+                    out.push_str(&formatted);
+                }
+                Err(err) => {
+                    block.error = Some(err);
+                    out.push_str(&code);
+                }
+            }
+            blocks.push(block);
+        } else {
+            out.push_str(&code);
+        }
+
+        if let Some(line) = lines.get(close) {
+            out.push_str(line);
+        }
+        index = close + 1;
+    }
+    (out, blocks)
+}
@@ -0,0 +1,122 @@
+use crate::tokens::Token;
+
+fn depth_change(token: &Token) -> isize {
+    match token {
+        Token::BraceOpen | Token::BraceSquareOpen | Token::ParenOpen => 1,
+        Token::BraceClose | Token::BraceSquareClosed | Token::ParenClose => -1,
+        _ => 0,
+    }
+}
+
+// The `{` of the match starting at `start`, and the `}` closing it
+fn match_block(tokens: &[Token], start: usize) -> Option<(usize, usize)> {
+    let open = start
+        + tokens[start..]
+            .iter()
+            .position(|e| e == &Token::BraceOpen || e == &Token::NewLine)?;
+    if tokens[open] != Token::BraceOpen {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        depth += depth_change(token);
+        if depth == 0 {
+            return Some((open, index));
+        }
+    }
+    None
+}
+
+fn is_match(token: &Token) -> bool {
+    token == &Token::Word("match".to_string())
+}
+
+// A match spanning multiple lines gets every arm on its own line, `1 => "one", 2 => "two"` is
+// split at the comma. The arms on their own lines don't need the commas between them
+pub fn normalise_match_arms(tokens: Vec<Token>) -> Vec<Token> {
+    let mut breaks = Vec::new();
+    let mut commas = Vec::new();
+
+    for (index, _) in tokens.iter().enumerate().filter(|(_, e)| is_match(e)) {
+        let Some((open, close)) = match_block(&tokens, index) else {
+            continue;
+        };
+        if !tokens[open..close].contains(&Token::NewLine) {
+            continue;
+        }
+
+        if tokens.get(open + 1) != Some(&Token::NewLine) {
+            breaks.push(open + 1);
+        }
+
+        let mut depth = 0;
+        for (index, token) in tokens.iter().enumerate().take(close).skip(open) {
+            depth += depth_change(token);
+            if depth == 1 && token == &Token::Comma {
+                commas.push(index);
+                if tokens.get(index + 1) != Some(&Token::NewLine) {
+                    breaks.push(index + 1);
+                }
+            }
+        }
+
+        if tokens[close - 1] != Token::NewLine {
+            breaks.push(close);
+        }
+    }
+
+    let mut out = Vec::with_capacity(tokens.len() + breaks.len());
+    for (index, token) in tokens.into_iter().enumerate() {
+        if breaks.contains(&index) {
+            out.push(Token::NewLine);
+        }
+        if !commas.contains(&index) {
+            out.push(token);
+        }
+    }
+    out
+}
+
+// Lines up the `=>` of the arms of a multi-line match, works on the formatted stack where the
+// whitespace and indentation are in place
+pub fn align_match_arms(stack: &mut Vec<Token>) {
+    let mut index = 0;
+
+    while index < stack.len() {
+        if !is_match(&stack[index]) {
+            index += 1;
+            continue;
+        }
+        let Some((open, close)) = match_block(stack, index) else {
+            index += 1;
+            continue;
+        };
+        if !stack[open..close].contains(&Token::NewLine) {
+            index += 1;
+            continue;
+        }
+
+        // position of every arrow at the depth of the arms with the width of the line before it
+        let mut arrows = Vec::new();
+        let mut depth = 0;
+        let mut width = 0;
+        for (i, token) in stack.iter().enumerate().take(close).skip(open) {
+            depth += depth_change(token);
+            match token {
+                Token::NewLine | Token::Tab(_) => width = 0,
+                Token::FatArrow if depth == 1 => arrows.push((i, width)),
+                _ => width += token.as_string().chars().count(),
+            }
+        }
+
+        let column = arrows.iter().map(|(_, w)| *w).max().unwrap_or_default();
+        for (i, w) in arrows.into_iter().rev() {
+            for _ in w..column {
+                stack.insert(i, Token::WhiteSpace);
+            }
+        }
+
+        index += 1;
+    }
+}
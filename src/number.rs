@@ -0,0 +1,95 @@
+use crate::config::{Case, Config};
+
+const FILESIZE_UNITS: &[&str] = &[
+    "b", "kb", "mb", "gb", "tb", "pb", "eb", "kib", "mib", "gib", "tib", "pib", "eib",
+];
+
+const DURATION_UNITS: &[&str] = &["ns", "us", "µs", "ms", "sec", "min", "hr", "day", "wk"];
+
+// splits `-1_000.5e3kb` in the number `-1_000.5e3` and its unit `kb`
+fn split_unit(s: &str) -> Option<(&str, &str)> {
+    let digits = |s: &str| {
+        s.char_indices()
+            .find(|(_, c)| !c.is_ascii_digit() && *c != '_')
+            .map(|(i, _)| i)
+            .unwrap_or(s.len())
+    };
+
+    let sign = s.starts_with(['-', '+']) as usize;
+    let mut end = sign + digits(&s[sign..]);
+    if end == sign || s[sign..].starts_with('_') {
+        return None;
+    }
+
+    // fraction, only when a digit follows the dot so ranges `1..10` are left alone
+    if s[end..].starts_with('.') {
+        let fraction = digits(&s[end + 1..]);
+        if fraction > 0 {
+            end += 1 + fraction;
+        }
+    }
+
+    // exponent `1e10`, `1.5E-3`
+    if s[end..].starts_with(['e', 'E']) {
+        let exp = &s[end + 1..];
+        let exp_sign = exp.starts_with(['-', '+']) as usize;
+        let exp_digits = digits(&exp[exp_sign..]);
+        if exp_digits > 0 {
+            end += 1 + exp_sign + exp_digits;
+        }
+    }
+
+    Some(s.split_at(end))
+}
+
+// `0xff` in its prefix, digits and radix
+fn radix_digits(s: &str) -> Option<(&str, &str, u32)> {
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (prefix, digits) = (s.get(..2)?, s.get(2..)?);
+
+    let radix = match prefix {
+        "0x" => 16,
+        "0o" => 8,
+        "0b" => 2,
+        _ => return None,
+    };
+    Some((prefix, digits, radix))
+}
+
+pub fn is_number(s: &str) -> bool {
+    // `0b` on its own is zero bytes
+    if let Some((_, digits, radix)) = radix_digits(s).filter(|(_, d, _)| !d.is_empty()) {
+        return digits.chars().all(|c| c.is_digit(radix) || c == '_');
+    }
+
+    match split_unit(s) {
+        Some((_, "")) => true,
+        Some((_, unit)) => {
+            FILESIZE_UNITS.contains(&unit.to_lowercase().as_str()) || DURATION_UNITS.contains(&unit)
+        }
+        None => false,
+    }
+}
+
+fn apply_case(s: &str, case: Case) -> String {
+    match case {
+        Case::Preserve => s.to_string(),
+        Case::Lower => s.to_lowercase(),
+        Case::Upper => s.to_uppercase(),
+    }
+}
+
+// only filesize units are case insensitive, `10MS` is not a duration
+pub fn normalise_number(s: &str, config: &Config) -> String {
+    if radix_digits(s).is_some_and(|(prefix, digits, _)| prefix == "0x" && !digits.is_empty()) {
+        let (prefix, digits) = s.split_at(s.find('x').unwrap_or_default() + 1);
+        return format!("{}{}", prefix, apply_case(digits, config.hex_case));
+    }
+
+    match split_unit(s) {
+        Some((number, unit)) if FILESIZE_UNITS.contains(&unit.to_lowercase().as_str()) => {
+            format!("{}{}", number, apply_case(unit, config.unit_case))
+        }
+        _ => s.to_string(),
+    }
+}
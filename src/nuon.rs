@@ -0,0 +1,185 @@
+use crate::{
+    config::{Config, FinalNewline},
+    error::{FormatError, Span},
+    format_with_cursor, tokenize, tokenize_with_spans,
+    tokens::Token,
+};
+
+// tokens that only show up in code, a NUON document holds a single value
+fn is_code(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Pipe
+            | Token::Variable(_)
+            | Token::CellPath(_)
+            | Token::Operator(_)
+            | Token::Redirect(_)
+            | Token::External(_)
+            | Token::EnvShorthand(_)
+            | Token::FatArrow
+            | Token::Tag(_)
+            | Token::TagLong(_)
+            | Token::AttSomething(_)
+            | Token::ParenOpen
+            | Token::ParenClose
+            | Token::Dolar
+            | Token::Equals
+            | Token::Exc
+            | Token::LessThen
+            | Token::MoreThen
+    )
+}
+
+// bare words that are values rather than strings
+const KEYWORDS: &[&str] = &["true", "false", "null", "NaN", "inf", "-inf"];
+
+// A bare word inside a list or record is a string `[foo bar]`, at the top level it's a
+// command `ls` unless it's a keyword `true`, a number or a datetime `2024-01-01`
+fn is_data_word(word: &str, depth: usize) -> bool {
+    depth > 0 || KEYWORDS.contains(&word) || word.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn is_table_separator(token: &Token) -> bool {
+    token == &Token::Semicolon
+}
+
+// A command or pipeline, or more than one value at the top level, isn't data. `spans` are
+// those of the tokens that aren't layout
+fn check_data(buffer: &str, tokens: &[Token], spans: &[Span]) -> Result<(), FormatError> {
+    let mut depth = 0;
+    let mut values = 0;
+    let mut spans = spans.iter();
+    let mut prev = Span::new(0, 0);
+
+    for token in tokens {
+        // a comma has no span of its own, it is the first one after the token before it
+        let span = match token.is_layout() {
+            true => {
+                let found = token.as_string();
+                buffer[prev.end..].find(&found).map_or(prev, |e| {
+                    Span::new(prev.end + e, prev.end + e + found.len())
+                })
+            }
+            false => spans.next().copied().unwrap_or(prev),
+        };
+        prev = span;
+        let not_data = || FormatError::NotData {
+            found: token.as_string(),
+            span,
+        };
+        let data = match token {
+            Token::Word(w) | Token::Tag(w) => is_data_word(w, depth),
+            _ => !is_code(token),
+        };
+        if !data {
+            return Err(not_data());
+        }
+
+        match token {
+            Token::NewLine | Token::WhiteSpace | Token::Tab(_) | Token::CommentBlock(_) => (),
+            Token::BraceClose | Token::BraceSquareClosed => depth -= 1,
+            _ if depth > 0 => {
+                if matches!(token, Token::BraceOpen | Token::BraceSquareOpen) {
+                    depth += 1;
+                }
+            }
+            // `a: 1` or `1, 2` at the top level
+            Token::Colon | Token::Comma => return Err(not_data()),
+            _ => {
+                values += 1;
+                if values > 1 {
+                    return Err(not_data());
+                }
+                if matches!(token, Token::BraceOpen | Token::BraceSquareOpen) {
+                    depth += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// everything on one line the way `to nuon` writes it, `{a: 1, b: [1, 2]}`
+fn compact(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut prev: Option<&Token> = None;
+
+    for token in tokens.iter().filter(|e| {
+        !matches!(
+            e,
+            Token::NewLine | Token::WhiteSpace | Token::Tab(_) | Token::CommentBlock(_)
+        )
+    }) {
+        let ends_value = prev.is_some_and(|e| {
+            !matches!(
+                e,
+                Token::BraceOpen | Token::BraceSquareOpen | Token::Comma | Token::Colon
+            ) && !is_table_separator(e)
+        });
+        let starts_value = !matches!(
+            token,
+            Token::BraceClose | Token::BraceSquareClosed | Token::Comma | Token::Colon
+        ) && !is_table_separator(token);
+
+        if ends_value && starts_value {
+            out.push_str(", ");
+        }
+        // no trailing comma `[1, 2,]`
+        if matches!(token, Token::BraceClose | Token::BraceSquareClosed) {
+            out.truncate(out.trim_end_matches(", ").len());
+        }
+        match token {
+            Token::Comma => out.push_str(", "),
+            Token::Colon => out.push_str(": "),
+            _ if is_table_separator(token) => out.push_str("; "),
+            _ => out.push_str(&token.as_string()),
+        }
+        prev = Some(token);
+    }
+    out
+}
+
+// Formats a NUON document with the layout rules of lists, records and tables, or on a single
+// line with `compact`. Documents holding commands or pipelines are rejected
+pub fn format_nuon(
+    buffer: &str,
+    config: &Config,
+    compact_output: bool,
+) -> Result<String, FormatError> {
+    let (formatted, _) = format_nuon_with_cursor(buffer, config, None)?;
+    if !compact_output {
+        return Ok(formatted);
+    }
+
+    let final_newline = match config.final_newline {
+        FinalNewline::Always => true,
+        FinalNewline::Never => false,
+        FinalNewline::Preserve => buffer.ends_with('\n'),
+    };
+    let config = Config {
+        nuon: true,
+        ..config.clone()
+    };
+    let mut output = compact(&tokenize(buffer, &config));
+    if final_newline && !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+// `format_nuon` with the layout rules that also tells where the byte `cursor` of `buffer` ends up
+pub fn format_nuon_with_cursor(
+    buffer: &str,
+    config: &Config,
+    cursor: Option<usize>,
+) -> Result<(String, Option<usize>), FormatError> {
+    let config = Config {
+        nuon: true,
+        ..config.clone()
+    };
+    let formatted = format_with_cursor(buffer, &config, cursor)?;
+    let (tokens, spans) = tokenize_with_spans(buffer, &config);
+    check_data(buffer, &tokens, &spans)?;
+
+    Ok(formatted)
+}
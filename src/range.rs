@@ -0,0 +1,275 @@
+use std::ops::Range;
+
+use crate::{
+    config::{Config, FinalNewline, LineEnding},
+    error::FormatError,
+    format_buffer_at,
+    validate::structure,
+};
+
+fn line_start(buffer: &str, index: usize) -> usize {
+    buffer[..index]
+        .rfind('\n')
+        .map(|e| e + 1)
+        .unwrap_or_default()
+}
+
+// end of the line `index` is on, before the `\n` or `\r\n`
+fn line_end(buffer: &str, index: usize) -> usize {
+    let end = buffer[index..]
+        .find('\n')
+        .map(|e| index + e)
+        .unwrap_or(buffer.len());
+    match buffer[..end].ends_with('\r') {
+        true => end - 1,
+        false => end,
+    }
+}
+
+// the line after the one `index` is on
+fn next_line(buffer: &str, index: usize) -> Option<usize> {
+    buffer[index..].find('\n').map(|e| index + e + 1)
+}
+
+fn is_blank(buffer: &str, start: usize) -> bool {
+    buffer[start..line_end(buffer, start)].trim().is_empty()
+}
+
+// Byte range of the lines `start..=end`, counted from 1, the lines may run past the end of the
+// file but have to start in it
+pub fn line_range(buffer: &str, start: usize, end: usize) -> Result<Range<usize>, FormatError> {
+    let len = buffer.lines().count().max(1);
+    if start > len {
+        return Err(FormatError::OutsideFile {
+            start,
+            end,
+            len,
+            unit: "line",
+        });
+    }
+    let offset = |line: usize| {
+        buffer
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(|e| e.len())
+            .sum::<usize>()
+    };
+    let start = offset(start);
+
+    Ok(start..line_end(buffer, offset(end).max(start)))
+}
+
+// Grows whole lines `start..end` until they hold complete statements, no string or pair of
+// delimiters is cut in half and pipelines continued on another line are taken whole
+pub(crate) fn statements(
+    buffer: &str,
+    mut start: usize,
+    mut end: usize,
+) -> Result<Range<usize>, FormatError> {
+    let structure = structure(buffer)?;
+
+    loop {
+        let (old_start, old_end) = (start, end);
+
+        for (open, close) in &structure.pairs {
+            let (open, close) = (*open, *close);
+            if (start..end).contains(&open) != (start..end).contains(&close) {
+                start = start.min(line_start(buffer, open));
+                end = end.max(line_end(buffer, close));
+            }
+        }
+        // statements live in blocks, a selection within a list or subexpression takes all of it
+        let enclosing = structure
+            .pairs
+            .iter()
+            .filter(|(open, close)| *open < start && *close >= end)
+            .max();
+        if let Some((open, close)) = enclosing.filter(|(open, _)| !buffer[*open..].starts_with('{'))
+        {
+            start = line_start(buffer, *open);
+            end = end.max(line_end(buffer, *close));
+        }
+        for string in &structure.strings {
+            if (string.start < end) && (string.end > start) {
+                start = start.min(line_start(buffer, string.start));
+                end = end.max(line_end(buffer, string.end - 1));
+            }
+        }
+
+        // `ls\n| get name`
+        if start > 0 {
+            let prev = line_start(buffer, start - 1);
+            if buffer[prev..start].trim_end().ends_with('|')
+                || buffer[start..].trim_start().starts_with('|')
+            {
+                start = prev;
+            }
+        }
+        if let Some(next) = next_line(buffer, end) {
+            if buffer[start..end].trim_end().ends_with('|')
+                || buffer[next..line_end(buffer, next)]
+                    .trim_start()
+                    .starts_with('|')
+            {
+                end = line_end(buffer, next);
+            }
+        }
+
+        if (start, end) == (old_start, old_end) {
+            return Ok(start..end);
+        }
+    }
+}
+
+// Formats the statements overlapping the bytes `range` and leaves the rest of `buffer` as it is.
+// The statements are formatted inside as many blocks as they are nested in, so they get the
+// indentation they would have when formatting the whole file
+pub fn format_range(
+    buffer: &str,
+    range: Range<usize>,
+    config: &Config,
+) -> Result<String, FormatError> {
+    format_range_with_cursor(buffer, range, config, None).map(|(e, _)| e)
+}
+
+// `format_range` that also tells where the byte `cursor` of `buffer` ends up
+pub fn format_range_with_cursor(
+    buffer: &str,
+    range: Range<usize>,
+    config: &Config,
+    cursor: Option<usize>,
+) -> Result<(String, Option<usize>), FormatError> {
+    if range.start > buffer.len() {
+        return Err(FormatError::OutsideFile {
+            start: range.start,
+            end: range.end,
+            len: buffer.len(),
+            unit: "byte",
+        });
+    }
+    // offsets in the middle of a char are moved to its start
+    let boundary = |mut i: usize| {
+        i = i.min(buffer.len());
+        while !buffer.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let range = boundary(range.start)..boundary(range.end).max(boundary(range.start));
+
+    let mut start = line_start(buffer, range.start);
+    let mut end = match range.end > range.start && range.end == line_start(buffer, range.end) {
+        // a range ending at the start of a line doesn't take that line
+        true => line_end(buffer, range.end - 1),
+        false => line_end(buffer, range.end),
+    };
+
+    // blank lines around the selection stay as they are
+    while start < end && is_blank(buffer, start) {
+        start = next_line(buffer, start).unwrap_or(end).min(end);
+    }
+    while start < end && is_blank(buffer, line_start(buffer, end)) {
+        end = line_end(buffer, line_start(buffer, end).saturating_sub(1).max(start));
+    }
+    if start >= end {
+        crate::validate::validate(buffer)?;
+        return Ok((buffer.to_string(), cursor));
+    }
+
+    let statements = statements(buffer, start, end)?;
+    let depth = structure(buffer)?
+        .pairs
+        .iter()
+        .filter(|(open, close)| *open < statements.start && *close >= statements.end)
+        .count();
+
+    let crlf = match config.line_ending {
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+        LineEnding::Auto => buffer
+            .find('\n')
+            .is_some_and(|i| buffer[..i].ends_with('\r')),
+    };
+    let config = Config {
+        final_newline: FinalNewline::Never,
+        line_ending: LineEnding::Lf,
+        ..config.clone()
+    };
+
+    let wrapped = format!(
+        "{}{}{}",
+        "{\n".repeat(depth),
+        &buffer[statements.clone()],
+        "\n}".repeat(depth)
+    );
+    // a cursor in the statements is followed through the formatting, one after them moves with
+    // the end of the statements
+    let inside = cursor
+        .filter(|e| statements.contains(e) || *e == statements.end)
+        .map(|e| e - statements.start + 2 * depth);
+    let (formatted, inside) = format_buffer_at(&wrapped, &config, inside);
+    // the lines of the blocks wrapped around the statements
+    let lines = formatted.split('\n').collect::<Vec<_>>();
+    let Some(lines) = lines.get(depth..lines.len().saturating_sub(depth)) else {
+        return Ok((buffer.to_string(), cursor));
+    };
+    let prefix = lines_len(&formatted, depth);
+    let mut formatted = lines.join("\n");
+    let mut inside = inside.map(|e| e.saturating_sub(prefix).min(formatted.len()));
+    if crlf {
+        inside = inside.map(|e| e + formatted[..e].matches('\n').count());
+        formatted = formatted.replace('\n', "\r\n");
+    }
+
+    let cursor = match (cursor, inside) {
+        (_, Some(inside)) => Some(statements.start + inside),
+        (Some(cursor), None) if cursor > statements.end => {
+            Some(cursor + formatted.len() + statements.start - statements.end)
+        }
+        (cursor, None) => cursor,
+    };
+    Ok((
+        format!(
+            "{}{}{}",
+            &buffer[..statements.start],
+            formatted,
+            &buffer[statements.end..]
+        ),
+        cursor,
+    ))
+}
+
+// length of the first `count` lines of `text` with their `\n`
+fn lines_len(text: &str, count: usize) -> usize {
+    text.split_inclusive('\n').take(count).map(str::len).sum()
+}
+
+// Formats the statements on every range of lines `(start, end)`, counted from 1
+pub fn format_lines(
+    buffer: &str,
+    lines: &[(usize, usize)],
+    config: &Config,
+) -> Result<String, FormatError> {
+    format_lines_with_cursor(buffer, lines, config, None).map(|(e, _)| e)
+}
+
+// `format_lines` that also tells where the byte `cursor` of `buffer` ends up
+pub fn format_lines_with_cursor(
+    buffer: &str,
+    lines: &[(usize, usize)],
+    config: &Config,
+    mut cursor: Option<usize>,
+) -> Result<(String, Option<usize>), FormatError> {
+    crate::validate::validate(buffer)?;
+
+    // from the bottom up, so formatting a range doesn't move the lines of the ones before it
+    let mut lines = lines.to_vec();
+    lines.sort();
+
+    let mut buffer = buffer.to_string();
+    for (start, end) in lines.into_iter().rev() {
+        let range = line_range(&buffer, start, end)?;
+        (buffer, cursor) = format_range_with_cursor(&buffer, range, config, cursor)?;
+    }
+    Ok((buffer, cursor))
+}
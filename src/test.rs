@@ -1,4 +1,16 @@
-use crate::format_buffer;
+use crate::{
+    config::{Case, Config, ElsePlacement, FinalNewline, IndentStyle, LineEnding, ListSeparator},
+    diff::{apply, diff},
+    format_buffer, format_buffer_with_config, format_lines, format_range, format_range_with_cursor,
+    format_str, format_with_cursor,
+    git::parse_hunks,
+    line_range, lsp,
+    markdown::format_markdown,
+    nuon::format_nuon,
+    FormatError, Span,
+};
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::TextEdit;
 
 #[test]
 fn remove_leading_and_trailing_whitespace() {
@@ -9,11 +21,33 @@ fn remove_leading_and_trailing_whitespace() {
 
 #[test]
 fn remove_trailing_newline() {
-    let text = "let answer = 42\n";
-    let format_buffer = format_buffer(text.to_string());
+    let text = "let answer = 42\n\n\n";
+    let config = Config {
+        final_newline: FinalNewline::Never,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
     assert_eq!(format_buffer, "let answer = 42");
 }
 
+#[test]
+fn preserve_trailing_newline() {
+    let text = "let answer = 42\n\n\n";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let answer = 42\n");
+}
+
+#[test]
+fn add_trailing_newline() {
+    let text = "let answer = 42";
+    let config = Config {
+        final_newline: FinalNewline::Always,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let answer = 42\n");
+}
+
 #[test]
 fn ignore_comments() {
     let text = "#let answer=42   !";
@@ -150,7 +184,7 @@ fn forloop() {
 fn nesting_1() {
     let text = "$item | each {[$in.title $in.text ] } ";
     let format_buffer = format_buffer(text.to_string());
-    assert_eq!(format_buffer, "$item | each {[ $in.title $in.text ]}");
+    assert_eq!(format_buffer, "$item | each { [ $in.title $in.text ] }");
 }
 
 #[test]
@@ -174,7 +208,7 @@ fn nesting_3() {
 fn nesting_4() {
     let text = "$item | each {\n{\nd: [$in.title [$in.text] ],\nb:[$in.title $in.text]\n\t\t}\n} ";
     let format_buffer = format_buffer(text.to_string());
-    assert_eq!(format_buffer, "$item | each {\n\t\t{\n\t\t\t\td: [ $in.title [ $in.text ]],\n\t\t\t\tb: [ $in.title $in.text ]\n\t\t}\n}");
+    assert_eq!(format_buffer, "$item | each {\n\t\t{\n\t\t\t\td: [ $in.title [ $in.text ] ],\n\t\t\t\tb: [ $in.title $in.text ]\n\t\t}\n}");
 }
 
 #[test]
@@ -183,7 +217,7 @@ fn mismatch_square_braces_1() {
     let format_buffer = format_buffer(text.to_string());
     assert_eq!(
         format_buffer,
-        "$item | each {\n\t\t[[ $in.title $in.text ]\n\t\t}"
+        "$item | each {\n\t\t[ [ $in.title $in.text ]\n\t\t}"
     );
 }
 
@@ -193,7 +227,7 @@ fn mismatch_square_braces_2() {
     let format_buffer = format_buffer(text.to_string());
     assert_eq!(
         format_buffer,
-        "$item | each {\n\t\t[[{( $in.title $in.text ]\n\t\t\t\t\t\t}"
+        "$item | each {\n\t\t[ [ { ( $in.title $in.text ]\n\t\t\t\t\t\t}"
     );
 }
 
@@ -217,3 +251,1173 @@ fn indent_for_square_brace() {
     );
 }
 
+
+#[test]
+fn list_number_comma() {
+    let text = "let a = [1,2 ,3]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let a = [ 1, 2, 3 ]");
+}
+
+#[test]
+fn list_separator_commas() {
+    let text = "let a = [1 2, 3]";
+    let config = Config {
+        list_separator: ListSeparator::Commas,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [ 1, 2, 3 ]");
+}
+
+#[test]
+fn list_separator_spaces() {
+    let text = "let a = [1, 2, [3,4]]";
+    let config = Config {
+        list_separator: ListSeparator::Spaces,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [ 1 2 [ 3 4 ] ]");
+}
+
+#[test]
+fn list_no_padding() {
+    let text = "let a = [1 2 [3 4]]";
+    let config = Config {
+        square_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [1 2 [3 4]]");
+}
+
+#[test]
+fn list_break_long() {
+    let text = "let a = [one two three]";
+    let config = Config {
+        max_width: 16,
+        trailing_comma: true,
+        list_separator: ListSeparator::Commas,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "let a = [\n\t\tone,\n\t\ttwo,\n\t\tthree,\n]"
+    );
+}
+
+#[test]
+fn list_break_long_spaces() {
+    let text = "let a = [one two three]";
+    let config = Config {
+        max_width: 16,
+        trailing_comma: true,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [\n\t\tone\n\t\ttwo\n\t\tthree\n]");
+}
+
+#[test]
+fn list_break_counts_indent() {
+    let text = "let x = [1 2 3]\ndef a [] {\n  if true {\n    let x = [1 2 3]\n  }\n}";
+    let config = Config {
+        max_width: 22,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "let x = [ 1 2 3 ]\ndef a [] {\n\t\tif true {\n\t\t\t\tlet x = [\n\t\t\t\t\t\t1\n\t\t\t\t\t\t2\n\t\t\t\t\t\t3\n\t\t\t\t]\n\t\t}\n}"
+    );
+}
+
+#[test]
+fn table_rows_separator_commas() {
+    let text = "let t = [[a b]; [1 2] [3 4]]";
+    let config = Config {
+        list_separator: ListSeparator::Commas,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let t = [ [ a, b ]; [ 1, 2 ], [ 3, 4 ] ]");
+}
+
+#[test]
+fn list_keep_line_breaks() {
+    let text = "let a = [\n1, 2\n3,\n]";
+    let config = Config {
+        list_separator: ListSeparator::Spaces,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [\n\t\t1 2\n\t\t3\n]");
+}
+
+#[test]
+fn list_signature_untouched() {
+    let text = "def foo [x: int, y: int] {\n[$x $y]\n}";
+    let config = Config {
+        list_separator: ListSeparator::Commas,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "def foo [ x: int, y: int ] {\n\t\t[ $x, $y ]\n}"
+    );
+}
+
+#[test]
+fn no_paren_padding() {
+    let text = "(something=this=that)";
+    let config = Config {
+        paren_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "(something = this = that)");
+}
+
+#[test]
+fn record_padding_separate_from_block() {
+    let text = "if $a {\nlet r = {a: 1}\n$r | each { ls }\n}";
+    let config = Config {
+        record_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "if $a {\n\t\tlet r = {a: 1}\n\t\t$r | each { ls }\n}"
+    );
+}
+
+#[test]
+fn no_block_padding() {
+    let text = "$item | each { [$in.title] }";
+    let config = Config {
+        block_padding: false,
+        square_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "$item | each {[$in.title]}");
+}
+
+#[test]
+fn padding_of_nested_delimiters() {
+    let text = "$item | each {[$in.title]}\nlet a = ( [1] )\nlet b = [(1)]";
+    let config = Config {
+        square_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "$item | each { [$in.title] }\nlet a = ( [1] )\nlet b = [( 1 )]"
+    );
+
+    let config = Config {
+        paren_padding: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "$item | each { [ $in.title ] }\nlet a = ([ 1 ])\nlet b = [ (1) ]"
+    );
+}
+
+#[test]
+fn indent_with_spaces() {
+    let text = "if $a {\nif $b {\nls\n}\n}";
+    let config = Config {
+        indent_style: IndentStyle::Spaces,
+        indent_width: 4,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "if $a {\n    if $b {\n        ls\n    }\n}");
+}
+
+#[test]
+fn flags() {
+    let text = "ls  -la --all";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls -la --all");
+}
+
+#[test]
+fn flag_with_value() {
+    let text = "http get --headers=[a b] --max-time=\"10 sec\" $url";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "http get --headers=[ a b ] --max-time=\"10 sec\" $url"
+    );
+}
+
+#[test]
+fn flags_in_signature() {
+    let text = "def foo [x: int, --flag(-f): string, --other:int] {\necho $x\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "def foo [ x: int, --flag(-f): string, --other:int ] {\n\t\techo $x\n}"
+    );
+}
+
+#[test]
+fn dash_in_identifier() {
+    let text = "ls | str-join | into-datetime $a-b";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls | str-join | into-datetime $a-b");
+}
+
+#[test]
+fn binary_minus() {
+    let text = "let b = $a   -   1\nlet c = (1)-(2)";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let b = $a - 1\nlet c = ( 1 ) - ( 2 )");
+}
+
+#[test]
+fn unary_minus() {
+    let text = "let c = -1 + -$x\nlet d = 0..-1";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let c = -1 + -$x\nlet d = 0..-1");
+}
+
+#[test]
+fn numeric_literals() {
+    let text = "let a = [3.14,0xff, 1_000,10kb, 1.5GiB,500ms, 2day,1e10]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "let a = [ 3.14, 0xff, 1_000, 10kb, 1.5GiB, 500ms, 2day, 1e10 ]"
+    );
+}
+
+#[test]
+fn binary_literals() {
+    let text = "let a = [0x[ff 00], 0b[1010]]\nlet b = 0x[aB]";
+    let config = Config {
+        hex_case: Case::Lower,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [ 0x[ff 00], 0b[1010] ]\nlet b = 0x[ab]");
+}
+
+#[test]
+fn datetime_literals() {
+    let text = "let d = 2024-01-01T10:00:00+01:00\nlet e = [2024-01-01T10:00, 2024-01-01]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "let d = 2024-01-01T10:00:00+01:00\nlet e = [ 2024-01-01T10:00, 2024-01-01 ]"
+    );
+}
+
+#[test]
+fn numeric_case() {
+    let text = "let a = 0xfF + 1.5GiB + 10MB + 5MS";
+    let config = Config {
+        unit_case: Case::Lower,
+        hex_case: Case::Upper,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = 0xFF + 1.5gib + 10mb + 5MS");
+}
+
+#[test]
+fn symbol_operators() {
+    let text = "if $a =~ \"x\" {\n$a ++= [1]\n$b = 10 // 3 ** 2\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "if $a =~ \"x\" {\n\t\t$a ++= [ 1 ]\n\t\t$b = 10 // 3 ** 2\n}"
+    );
+}
+
+#[test]
+fn word_operators() {
+    let text = "$a not-in [1]   and   $b starts-with 'x' or $c mod 2 == 0";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "$a not-in [ 1 ] and $b starts-with 'x' or $c mod 2 == 0"
+    );
+}
+
+#[test]
+fn unspaced_operators() {
+    let text = "if $a==$b {}\n$x<=1 | where size>=10kb\n$a=~\"x\" or a!=b or $x>1\nlet c = [1+2 $a+$b (1)*2 $a**2]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "if $a == $b {}\n$x <= 1 | where size >= 10kb\n$a =~ \"x\" or a != b or $x > 1\nlet c = [ 1 + 2 $a + $b ( 1 ) * 2 $a ** 2 ]"
+    );
+}
+
+#[test]
+fn operators_inside_words() {
+    let text = "ls **/*.nu 2024/01 | str-join\nlet d = 2024-01-01\ncmd o+e>> log\n\ndef f [x: list<int>] {}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "ls **/*.nu 2024/01 | str-join\nlet d = 2024-01-01\ncmd o+e>> log\n\ndef f [ x: list<int> ] {}"
+    );
+}
+
+#[test]
+fn type_parameters_in_return_type() {
+    let text = "def f [x: record<a: list<string>>]: nothing -> list<string> {}\ndef g []: [string -> table<a: int>, nothing -> list<int>] {}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "def f [ x: record<a: list<string>> ] : nothing -> list<string> {}\n\ndef g [] : [ string -> table<a: int>, nothing -> list<int> ] {}"
+    );
+}
+
+#[test]
+fn word_operators_in_list() {
+    let text = "let ops = [and,or, in]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let ops = [ and, or, in ]");
+}
+
+#[test]
+fn operator_like_words() {
+    let text = "ls *.txt | each { $in }\nlet r = {in: 1}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls *.txt | each { $in }\nlet r = { in: 1 }");
+}
+
+#[test]
+fn ranges() {
+    let text = "let a = [1..10 0..<5 1..2..10 ..5 1.. -1..-5]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "let a = [ 1..10 0..<5 1..2..10 ..5 1.. -1..-5 ]"
+    );
+}
+
+#[test]
+fn range_before_semicolon() {
+    let text = "let a = 1..3; ls -la;cd /tmp ;let b = [1..3, 4]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "let a = 1..3; ls -la; cd /tmp; let b = [ 1..3, 4 ]");
+}
+
+#[test]
+fn range_with_variable_bounds() {
+    let text = "for i in $a..$b {\nlet r = ($x)..<($y + 1)\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "for i in $a..$b {\n\t\tlet r = ($x)..<($y + 1)\n}"
+    );
+}
+
+#[test]
+fn parent_dir_is_not_a_range() {
+    let text = "cd ../foo\ncd ..";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "cd ../foo\ncd ..");
+}
+
+#[test]
+fn cell_paths() {
+    let text = "let a = [$in.title? $x.0 $rec.\"quoted key\" $a.b?.0.c! $nu.home-path]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "let a = [ $in.title? $x.0 $rec.\"quoted key\" $a.b?.0.c! $nu.home-path ]"
+    );
+}
+
+#[test]
+fn cell_path_next_to_operators() {
+    let text = "if $a!=$b or $a.b?!~\"x\" {}\nlet a = $x; let b = [$x.0*2, $b*2]";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "if $a != $b or $a.b? !~ \"x\" {}\nlet a = $x; let b = [ $x.0 * 2, $b * 2 ]"
+    );
+}
+
+#[test]
+fn cell_path_followed_by_path() {
+    let text = "echo $env.PWD/foo\nsource $nu.default-config-dir/x.nu\nlet a = $b/2";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "echo $env.PWD/foo\nsource $nu.default-config-dir/x.nu\nlet a = $b / 2"
+    );
+}
+
+#[test]
+fn cell_path_on_subexpression() {
+    let text = "(ls).name | [1 2].0";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "( ls ).name | [ 1 2 ].0");
+}
+
+#[test]
+fn redirection() {
+    let text = "cmd o> out.txt\ncmd   out>out.txt err>>  err.log\ncmd o+e>> log";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "cmd o> out.txt\ncmd out> out.txt err>> err.log\ncmd o+e>> log"
+    );
+}
+
+#[test]
+fn stderr_pipe() {
+    let text = "cmd e>| less\ncmd o+e>|lines";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "cmd e>| less\ncmd o+e>| lines");
+}
+
+#[test]
+fn external_command() {
+    let text = "^git   status\n^\"my app\"  --flag";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "^git status\n^\"my app\" --flag");
+}
+
+#[test]
+fn external_command_from_variable() {
+    let text = "^$cmd   arg\n^($cmd) arg\n^$env.EDITOR file";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "^$cmd arg\n^( $cmd ) arg\n^$env.EDITOR file");
+}
+
+#[test]
+fn env_shorthand_after_semicolon() {
+    let text = "ls; FOO=bar cmd;A=1 cmd";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls; FOO=bar cmd; A=1 cmd");
+}
+
+#[test]
+fn env_shorthand() {
+    let text = "A=1 B=\"x y\"   cmd   arg | FOO=$x other";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "A=1 B=\"x y\" cmd arg | FOO=$x other");
+}
+
+#[test]
+fn match_single_line() {
+    let text = "match $x {1=>\"one\", _ => \"other\"}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "match $x { 1 => \"one\", _ => \"other\" }");
+}
+
+#[test]
+fn match_arms_on_own_line() {
+    let text = "match $x {1 => \"one\", 2 => \"two\"\n{a: $a} if $a > 1=>$a\n_ => 0}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "match $x {\n\t\t1 => \"one\"\n\t\t2 => \"two\"\n\t\t{ a: $a } if $a > 1 => $a\n\t\t_ => 0\n}"
+    );
+}
+
+#[test]
+fn match_arms_split_drop_commas() {
+    let text = "match $x {\n1 => \"a\", 2 => \"b\", 3 => \"c\",\n[$a, $b] => $a,\n_ => \"d\"\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "match $x {\n\t\t1 => \"a\"\n\t\t2 => \"b\"\n\t\t3 => \"c\"\n\t\t[ $a, $b ] => $a\n\t\t_ => \"d\"\n}"
+    );
+}
+
+#[test]
+fn match_align_arms() {
+    let text = "match $x {\n1 => \"one\"\n[$first, ..$rest] => $rest\n_ => {\nprint 0\n}\n}";
+    let config = Config {
+        align_match_arms: true,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "match $x {\n\t\t1                   => \"one\"\n\t\t[ $first, ..$rest ] => $rest\n\t\t_                   => {\n\t\t\t\tprint 0\n\t\t}\n}"
+    );
+}
+
+#[test]
+fn else_same_line() {
+    let text = "if $a == 1 {\nprint 1\n}\nelse if $a == 2\n{\nprint 2\n} else\n{\nprint 3\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "if $a == 1 {\n\t\tprint 1\n} else if $a == 2 {\n\t\tprint 2\n} else {\n\t\tprint 3\n}"
+    );
+}
+
+#[test]
+fn else_next_line() {
+    let text = "if $a {\nprint 1\n} else if $b {\nprint 2\n}\n\n\nelse {\nprint 3\n}";
+    let config = Config {
+        else_placement: ElsePlacement::NextLine,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "if $a {\n\t\tprint 1\n}\nelse if $b {\n\t\tprint 2\n}\nelse {\n\t\tprint 3\n}"
+    );
+}
+
+#[test]
+fn collapse_blank_lines() {
+    let text = "\n\nuse std\n\n\n\n\nlet a = 1\nif $a {\n\nls\n\n\nls\n\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "use std\n\nlet a = 1\nif $a {\n\t\tls\n\n\t\tls\n}"
+    );
+}
+
+#[test]
+fn keep_blank_lines() {
+    let text = "let a = 1\n\n\n\nif $a {\n\nls\n}";
+    let config = Config {
+        max_blank_lines: 2,
+        trim_block_blank_lines: false,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = 1\n\n\nif $a {\n\n\t\tls\n}");
+}
+
+#[test]
+fn blank_line_between_definitions() {
+    let text = "let a = 1\n# doc for foo\ndef foo [] {\nls\n}\n\n\n\nexport def bar [] {\nls\n}\ndef baz [] {}\nbar";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "let a = 1\n# doc for foo\ndef foo [] {\n\t\tls\n}\n\nexport def bar [] {\n\t\tls\n}\n\ndef baz [] {}\nbar"
+    );
+}
+
+#[test]
+fn crlf_input() {
+    let text = "if $a {\r\nls\r\n}\r\n";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "if $a {\r\n\t\tls\r\n}\r\n");
+}
+
+#[test]
+fn crlf_to_lf() {
+    let text = "let a = [1\r\n2]\r\n";
+    let config = Config {
+        line_ending: LineEnding::Lf,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "let a = [ 1\n\t\t2 ]\n");
+}
+
+#[test]
+fn path_ends_at_delimiter() {
+    let text = "{ cd /tmp}\nuse ./lib.nu\nls";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "{ cd /tmp }\nuse ./lib.nu\nls");
+}
+
+#[test]
+fn import_path_ends_at_delimiter() {
+    let text = "{ use ./lib.nu}\n(overlay use std/log)\nls; source ../env.nu\ncd /tmp";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "{ use ./lib.nu }\n( overlay use std/log )\nls; source ../env.nu\ncd /tmp"
+    );
+}
+
+#[test]
+fn path_ends_at_newline() {
+    let text = "(ls /tmp)\ndef a [] {\nls /tmp\nls\n}\ntry {\nrm -rf /tmp/foo\n} catch {|e|\nls\n}\ncd /tmp\n\n\n\nls";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "( ls /tmp )\ndef a [] {\n\t\tls /tmp\n\t\tls\n}\ntry {\n\t\trm -rf /tmp/foo\n} catch { | e |\n\t\tls\n}\ncd /tmp\n\nls"
+    );
+}
+
+#[test]
+fn sort_imports() {
+    let text = "use ./lib.nu\nuse std [log assert]\nuse ./lib.nu\noverlay use ../foo.nu\nuse std/log [warn, info]\nuse my-mod\n\nuse b\nuse a\nls";
+    let config = Config {
+        sort_imports: true,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(
+        format_buffer,
+        "use std [ assert log ]\nuse std/log [ info, warn ]\nuse my-mod\noverlay use ../foo.nu\nuse ./lib.nu\n\nuse a\nuse b\nls"
+    );
+}
+
+#[test]
+fn sort_imports_multiline_members() {
+    let text = "use zz\nuse aa [\n  y\n  x\n]\nls";
+    let config = Config {
+        sort_imports: true,
+        ..Config::default()
+    };
+    let format_buffer = format_buffer_with_config(text.to_string(), &config);
+    assert_eq!(format_buffer, "use aa [\n\t\tx\n\t\ty\n]\nuse zz\nls");
+}
+
+#[test]
+fn format_str_matches_format_buffer() {
+    let text = "let a = [1,2]\nls";
+    let formatted = format_str(text, &Config::default()).unwrap();
+    assert_eq!(formatted, format_buffer(text.to_string()));
+}
+
+#[test]
+fn error_unterminated_string() {
+    let text = "let a = 1\nlet b = \"abc\n";
+    let err = format_str(text, &Config::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::UnterminatedString { quote: '"', span } if span == Span::new(18, 19)
+    ));
+    assert_eq!(Span::new(18, 19).line_col(text), (2, 9));
+}
+
+#[test]
+fn error_unbalanced_delimiter() {
+    let err = format_str("ls (a]", &Config::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::UnbalancedDelimiter {
+            open: Some(('(', open)),
+            close: Some((']', close)),
+        } if open == Span::new(3, 4) && close == Span::new(5, 6)
+    ));
+
+    let err = format_str("def a [] {\n\tls\n", &Config::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::UnbalancedDelimiter { open: Some(('{', _)), close: None }
+    ));
+
+    let err = format_str("ls\n}", &Config::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::UnbalancedDelimiter { open: None, close: Some(('}', _)) }
+    ));
+}
+
+#[test]
+fn error_invalid_token() {
+    let err = format_str("ls\u{7}", &Config::default()).unwrap_err();
+    assert!(matches!(err, FormatError::InvalidToken { token: '\u{7}', .. }));
+}
+
+#[test]
+fn no_error_on_delimiters_in_strings_and_comments() {
+    let text = "# don't (\nls \"a\\\"(\" '[' `{` # }\nhttp://a#b";
+    assert!(format_str(text, &Config::default()).is_ok());
+}
+
+#[test]
+fn diff_is_minimal() {
+    let old = "def a [] {\nls\n}\nlet b = [1,2]";
+    let new = format_buffer(old.to_string());
+    let edits = diff(old, &new);
+    assert_eq!(apply(old, &edits), new);
+    // the indent of `ls` and the padding and spaces of the list
+    assert_eq!(edits.len(), 4);
+    assert_eq!(edits[0].text, "\t\t");
+}
+
+// an in process client talking to the language server, `requests` are (method, params) sent
+// after opening `text`, the results of every request are returned
+fn lsp_session(text: &str, requests: &[(&str, serde_json::Value)]) -> Vec<serde_json::Value> {
+    let (server, client) = Connection::memory();
+    let handle = std::thread::spawn(move || lsp::serve(server, &Config::default()).unwrap());
+
+    let send_request = |id: i32, method: &str, params: serde_json::Value| {
+        let request = Request::new(RequestId::from(id), method.to_string(), params);
+        client.sender.send(Message::Request(request)).unwrap();
+        match client.receiver.recv().unwrap() {
+            Message::Response(response) => response.result.unwrap_or_default(),
+            message => panic!("unexpected message {:?}", message),
+        }
+    };
+    let notify = |method: &str, params: serde_json::Value| {
+        let notification = Notification::new(method.to_string(), params);
+        client.sender.send(Message::Notification(notification)).unwrap();
+    };
+
+    send_request(0, "initialize", serde_json::json!({ "capabilities": {} }));
+    notify("initialized", serde_json::json!({}));
+    notify(
+        "textDocument/didOpen",
+        serde_json::json!({ "textDocument": {
+            "uri": "file:///a.nu", "languageId": "nushell", "version": 1, "text": text
+        }}),
+    );
+
+    let results = requests
+        .iter()
+        .enumerate()
+        .map(|(id, (method, params))| send_request(id as i32 + 1, method, params.clone()))
+        .collect();
+
+    send_request(99, "shutdown", serde_json::Value::Null);
+    notify("exit", serde_json::Value::Null);
+    handle.join().unwrap();
+    results
+}
+
+fn lsp_edits(value: serde_json::Value) -> Vec<TextEdit> {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn lsp_formatting() {
+    let document = serde_json::json!({ "uri": "file:///a.nu" });
+    let options = serde_json::json!({ "tabSize": 4, "insertSpaces": false });
+    let results = lsp_session(
+        "def a [] {\nls\n}\nlet b = [1,2]",
+        &[(
+            "textDocument/formatting",
+            serde_json::json!({ "textDocument": document, "options": options }),
+        )],
+    );
+
+    let edits = lsp_edits(results[0].clone());
+    assert_eq!(edits.len(), 4);
+    assert_eq!(edits[0].range.start, lsp_types::Position::new(1, 0));
+    assert_eq!(edits[0].new_text, "\t");
+}
+
+#[test]
+fn lsp_formatting_with_spaces() {
+    let document = serde_json::json!({ "uri": "file:///a.nu" });
+    let options = serde_json::json!({ "tabSize": 4, "insertSpaces": true });
+    let results = lsp_session(
+        "def a [] {\nls\n}",
+        &[(
+            "textDocument/formatting",
+            serde_json::json!({ "textDocument": document, "options": options }),
+        )],
+    );
+
+    let edits = lsp_edits(results[0].clone());
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].new_text, "    ");
+}
+
+#[test]
+fn lsp_range_formatting() {
+    let document = serde_json::json!({ "uri": "file:///a.nu" });
+    let options = serde_json::json!({ "tabSize": 4, "insertSpaces": false });
+    let range = serde_json::json!({
+        "start": { "line": 3, "character": 0 }, "end": { "line": 3, "character": 13 }
+    });
+    let results = lsp_session(
+        "def a [] {\nls\n}\nlet b = [1,2]",
+        &[(
+            "textDocument/rangeFormatting",
+            serde_json::json!({ "textDocument": document, "range": range, "options": options }),
+        )],
+    );
+
+    let edits = lsp_edits(results[0].clone());
+    assert!(edits.iter().all(|e| e.range.start.line == 3));
+    assert!(!edits.is_empty());
+}
+
+#[test]
+fn lsp_on_type_formatting() {
+    let document = serde_json::json!({ "uri": "file:///a.nu" });
+    let options = serde_json::json!({ "tabSize": 4, "insertSpaces": false });
+    let results = lsp_session(
+        "def a [] {\nls\n}\nlet b = [1,2]\n",
+        &[
+            (
+                "textDocument/onTypeFormatting",
+                serde_json::json!({
+                    "textDocument": document, "position": { "line": 2, "character": 1 },
+                    "ch": "}", "options": options
+                }),
+            ),
+            (
+                "textDocument/onTypeFormatting",
+                serde_json::json!({
+                    "textDocument": document, "position": { "line": 4, "character": 0 },
+                    "ch": "\n", "options": options
+                }),
+            ),
+        ],
+    );
+
+    let block = lsp_edits(results[0].clone());
+    assert_eq!(block.len(), 1);
+    assert_eq!(block[0].range.start, lsp_types::Position::new(1, 0));
+    assert_eq!(block[0].new_text, "\t");
+
+    let line = lsp_edits(results[1].clone());
+    assert!(line.iter().all(|e| e.range.start.line == 3));
+    // the padding and spaces of the list, the blank line above it is outside of the line
+    assert_eq!(line.len(), 3);
+}
+
+
+#[test]
+fn range_nested_statement() {
+    let text = "def a [] {\nls|get a\n  let x = [1,2]\n}\nlet b = [1,2]\n";
+    let range = line_range(text, 3, 3).unwrap();
+    let format_buffer = format_range(text, range, &Config::default()).unwrap();
+    assert_eq!(
+        format_buffer,
+        "def a [] {\nls|get a\n\t\tlet x = [ 1, 2 ]\n}\nlet b = [1,2]\n"
+    );
+}
+
+#[test]
+fn range_grows_to_whole_statement() {
+    let text = "let b = [1,2]\nif true {\nls\n}\nlet c = [1,2]";
+    let format_buffer = format_range(text, line_range(text, 2, 2).unwrap(), &Config::default()).unwrap();
+    assert_eq!(
+        format_buffer,
+        "let b = [1,2]\nif true {\n\t\tls\n}\nlet c = [1,2]"
+    );
+
+    let text = "ls\n| get name|sort\nlet c = [1,2]";
+    let format_buffer = format_range(text, 0..2, &Config::default()).unwrap();
+    assert_eq!(format_buffer, "ls\n| get name | sort\nlet c = [1,2]");
+}
+
+#[test]
+fn range_crlf() {
+    let text = "let b = [1,2]\r\n{\r\nls|get a\r\n}\r\n";
+    let format_buffer = format_range(text, line_range(text, 3, 3).unwrap(), &Config::default()).unwrap();
+    assert_eq!(format_buffer, "let b = [1,2]\r\n{\r\n\t\tls | get a\r\n}\r\n");
+}
+
+#[test]
+fn range_outside_file() {
+    let text = "ls\nls\n";
+    let err = line_range(text, 3, 4).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::OutsideFile { start: 3, end: 4, len: 2, .. }
+    ));
+    assert_eq!(err.to_string(), "the range 3:4 is outside the file, which ends at line 2");
+    assert_eq!(line_range(text, 2, 9).unwrap(), 3..6);
+
+    let err = format_range(text, 7..9, &Config::default()).unwrap_err();
+    assert!(matches!(err, FormatError::OutsideFile { len: 6, .. }));
+}
+
+#[test]
+fn git_hunks() {
+    let diff = "diff --git a/a.nu b/a.nu\n--- a/a.nu\n+++ b/a.nu\n@@ -2 +2 @@\n-let b = [1,2]\n+let b = [3,4]\n@@ -5,2 +5,0 @@ def a\n-ls\n-ls\n@@ -9,0 +8,3 @@\n+a\n+b\n+c\n";
+    assert_eq!(parse_hunks(diff), vec![(2, 2), (8, 10)]);
+}
+
+// the output of `git diff --unified=0` with the context line git adds after every hunk header and
+// a file without a final newline on both sides
+#[test]
+fn git_diff_output() {
+    let new = "let a = [1,2]\nls|get b\nlet b = 1\nlet c = [3,4]\nlet d = [5,6]";
+    let diff = "diff --git a/a.nu b/a.nu\nindex 91d2cba..4a50b1f 100644\n--- a/a.nu\n+++ b/a.nu\n@@ -2 +2 @@ let a = [1,2]\n-ls|get a\n+ls|get b\n@@ -4,3 +4,2 @@ let b = 1\n-ls\n-ls\n-let c = 2\n\\ No newline at end of file\n+let c = [3,4]\n+let d = [5,6]\n\\ No newline at end of file\n";
+
+    let lines = parse_hunks(diff);
+    assert_eq!(lines, vec![(2, 2), (4, 5)]);
+    let format_buffer = format_lines(new, &lines, &Config::default()).unwrap();
+    assert_eq!(
+        format_buffer,
+        "let a = [1,2]\nls | get b\nlet b = 1\nlet c = [ 3, 4 ]\nlet d = [ 5, 6 ]"
+    );
+}
+
+#[test]
+fn format_changed_lines() {
+    let text = "let a = [1,2]\nlet b = [3,4]\nlet c = [1,2]\nlet d = [1,2]";
+    let format_buffer = format_lines(text, &[(2, 2), (4, 4)], &Config::default()).unwrap();
+    assert_eq!(
+        format_buffer,
+        "let a = [1,2]\nlet b = [ 3, 4 ]\nlet c = [1,2]\nlet d = [ 1, 2 ]"
+    );
+}
+
+#[test]
+fn edits_as_json() {
+    let old = "let é = [1,2]";
+    let new = format_buffer(old.to_string());
+    let edits = diff(old, &new)
+        .iter()
+        .map(|e| e.to_json(old))
+        .collect::<Vec<_>>();
+
+    assert_eq!(edits.len(), 3);
+    assert_eq!(
+        edits[0],
+        serde_json::json!({
+            "range": {
+                "start": { "line": 0, "character": 9 },
+                "end": { "line": 0, "character": 9 },
+            },
+            "newText": " ",
+            "start": 10,
+            "end": 10,
+        })
+    );
+}
+
+#[test]
+fn cursor_follows_formatting() {
+    let old = "def a [] {\nls|get a\n}";
+    let config = Config::default();
+    let cursor = |cursor| format_with_cursor(old, &config, Some(cursor)).unwrap();
+    assert_eq!(cursor(0).0, "def a [] {\n\t\tls | get a\n}");
+
+    // on `g`, on `l` after the indent is inserted and at the end
+    assert_eq!(cursor(14).1, Some(18));
+    assert_eq!(cursor(11).1, Some(13));
+    assert_eq!(cursor(old.len()).1, Some(old.len() + 4));
+
+    // inside whitespace that is replaced, right after a token
+    let (_, cursor) = format_with_cursor("ls    |  get a", &config, Some(4)).unwrap();
+    assert_eq!(cursor, Some(3));
+    let (_, cursor) = format_with_cursor("ls    |  get a", &config, Some(2)).unwrap();
+    assert_eq!(cursor, Some(2));
+}
+
+#[test]
+fn cursor_follows_reordered_line() {
+    let config = Config {
+        sort_imports: true,
+        ..Config::default()
+    };
+    let text = "use zeta [x]\nuse alpha [y]";
+    let (format_buffer, cursor) = format_with_cursor(text, &config, Some(5)).unwrap();
+    assert_eq!(format_buffer, "use alpha [ y ]\nuse zeta [ x ]");
+    assert_eq!(&format_buffer[cursor.unwrap()..], "eta [ x ]");
+
+    // in a region that isn't formatted and after it, with CRLF line endings
+    let text = "# nufmt: skip\r\nls|get   a\r\nls|get b";
+    let (format_buffer, cursor) = format_with_cursor(text, &config, Some(22)).unwrap();
+    assert_eq!(format_buffer, "# nufmt: skip\r\nls|get   a\r\nls | get b");
+    assert_eq!(cursor, Some(22));
+    let (_, cursor) = format_with_cursor(text, &config, Some(30)).unwrap();
+    assert_eq!(&format_buffer[cursor.unwrap()..], "get b");
+}
+
+#[test]
+fn cursor_in_range() {
+    let text = "ls|get a\nif true {\nls|get b\n}\nls|get c";
+    let config = Config::default();
+    let range = line_range(text, 3, 3).unwrap();
+
+    let (format_buffer, cursor) =
+        format_range_with_cursor(text, range.clone(), &config, Some(22)).unwrap();
+    assert_eq!(format_buffer, "ls|get a\nif true {\n\t\tls | get b\n}\nls|get c");
+    assert_eq!(&format_buffer[cursor.unwrap()..], "get b\n}\nls|get c");
+
+    // before and after the statements
+    let (_, cursor) = format_range_with_cursor(text, range.clone(), &config, Some(3)).unwrap();
+    assert_eq!(cursor, Some(3));
+    let (_, cursor) = format_range_with_cursor(text, range, &config, Some(33)).unwrap();
+    assert_eq!(&format_buffer[cursor.unwrap()..], "get c");
+}
+
+#[test]
+fn directive_off_on() {
+    let text = "def a [] {\n# nufmt: off\nlet t = [\n  [a,   b];\n  [1,   2]\n]\n   # nufmt: on\nls|get a\n}";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "def a [] {\n# nufmt: off\nlet t = [\n  [a,   b];\n  [1,   2]\n]\n   # nufmt: on\n\t\tls | get a\n}"
+    );
+}
+
+#[test]
+fn directive_off_until_end() {
+    let text = "ls|get a\r\n#nufmt: off\r\nls|get   a  \r\n";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls | get a\r\n#nufmt: off\r\nls|get   a  \r\n");
+}
+
+#[test]
+fn directive_skip() {
+    let text = "# nufmt: skip\nlet x = [1,2,\n    3]\nlet y = [1,2]\n\t# nufmt: skip\nls|get a";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "# nufmt: skip\nlet x = [1,2,\n    3]\nlet y = [ 1, 2 ]\n\t# nufmt: skip\nls|get a"
+    );
+}
+
+#[test]
+fn directive_cutting_block() {
+    let text = "def a [] {\nls\n# nufmt: off\n}\ndef b [] {\n   ls\n}\n# nufmt: on\nls|get a";
+    let err = format_str(text, &Config::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::CutByDirective { delimiter: '}', span } if span == Span::new(27, 28)
+    ));
+
+    // without an `on` the region ends with the block
+    let text = "def a [] {\n# nufmt: off\nlet t = [1,   2]\n}\nls|get a";
+    let format_buffer = format_str(text, &Config::default()).unwrap();
+    assert_eq!(
+        format_buffer,
+        "def a [] {\n# nufmt: off\nlet t = [1,   2]\n}\nls | get a"
+    );
+}
+
+#[test]
+fn directive_skipped_definition() {
+    let text = "# nufmt: skip\ndef a [] {  ls }\ndef b [] { ls|get a }";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "# nufmt: skip\ndef a [] {  ls }\n\ndef b [] { ls | get a }"
+    );
+}
+
+#[test]
+fn directive_like_comment() {
+    let text = "# nufmt: skip\nls|get a\n# nufmt: verbatim 0\nls|get b";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(
+        format_buffer,
+        "# nufmt: skip\nls|get a\n# nufmt: verbatim 0\nls | get b"
+    );
+}
+
+#[test]
+fn markdown_blocks() {
+    let text = "# T\n\n```nu\nls|get a\n```\n\n- item\n\n  ~~~nushell\n  def a [] {\n  ls\n  }\n  ~~~\n\n```lua\nx  =  1\n```\n";
+    let (format_buffer, blocks) = format_markdown(text, &Config::default());
+    assert_eq!(
+        format_buffer,
+        "# T\n\n```nu\nls | get a\n```\n\n- item\n\n  ~~~nushell\n  def a [] {\n  \t\tls\n  }\n  ~~~\n\n```lua\nx  =  1\n```\n"
+    );
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks.iter().all(|e| e.changed && e.error.is_none()));
+    assert_eq!(blocks[1].line, 9);
+}
+
+#[test]
+fn markdown_block_that_fails() {
+    let text = "```nu\nls (a]\n```\n```nu\nls\n```";
+    let (format_buffer, blocks) = format_markdown(text, &Config::default());
+    assert_eq!(format_buffer, text);
+    assert!(matches!(
+        blocks[0].error,
+        Some(FormatError::UnbalancedDelimiter { .. })
+    ));
+    assert!(!blocks[1].changed);
+}
+
+#[test]
+fn nuon_document() {
+    let text = "{a: 1, b: [1,2,3,], c: {x: foo/bar}, t: [[a b]; [1 2]]}\n";
+    let format_buffer = format_nuon(text, &Config::default(), false).unwrap();
+    assert_eq!(
+        format_buffer,
+        "{ a: 1, b: [ 1, 2, 3 ], c: { x: foo/bar }, t: [ [ a b ]; [ 1 2 ] ] }\n"
+    );
+
+    let format_buffer = format_nuon(text, &Config::default(), true).unwrap();
+    assert_eq!(
+        format_buffer,
+        "{a: 1, b: [1, 2, 3], c: {x: foo/bar}, t: [[a, b]; [1, 2]]}\n"
+    );
+}
+
+#[test]
+fn nuon_paths_and_datetimes() {
+    let text = "{d: 2024-01-01T10:00:00+01:00, url: http://a.com, dir: /tmp, \"a b\": C:/Users}";
+    let format_buffer = format_nuon(text, &Config::default(), false).unwrap();
+    assert_eq!(
+        format_buffer,
+        "{ d: 2024-01-01T10:00:00+01:00, url: http://a.com, dir: /tmp, \"a b\": C:/Users }"
+    );
+
+    // scripts keep their layout
+    let text = "{dir:/tmp, \"a b\": 1}";
+    let format_buffer = format_buffer_with_config(text.to_string(), &Config::default());
+    assert_eq!(format_buffer, "{ dir:/tmp, \"a b\": 1 }");
+}
+
+#[test]
+fn nuon_binary_and_keywords() {
+    let text = "{a: 0x[ff 00], b: [true, null, -inf], t: [[name size]; [\"a\" 1]]}";
+    let format_buffer = format_nuon(text, &Config::default(), false).unwrap();
+    assert_eq!(
+        format_buffer,
+        "{ a: 0x[ff 00], b: [ true, null, -inf ], t: [ [ name size ]; [ \"a\" 1 ] ] }"
+    );
+
+    let format_buffer = format_nuon("0x[ff 00]", &Config::default(), true).unwrap();
+    assert_eq!(format_buffer, "0x[ff 00]");
+}
+
+#[test]
+fn raw_string() {
+    let text = "ls r#'a # {'#\nls";
+    let format_buffer = format_buffer(text.to_string());
+    assert_eq!(format_buffer, "ls r#'a # {'#\nls");
+}
+
+#[test]
+fn nuon_bare_and_raw_strings() {
+    let text = "{a: foo, b: [foo bar], c: some-word, d: r#'it's'#}";
+    let format_buffer = format_nuon(text, &Config::default(), false).unwrap();
+    assert_eq!(
+        format_buffer,
+        "{ a: foo, b: [ foo bar ], c: some-word, d: r#'it's'# }"
+    );
+
+    let format_buffer = format_nuon("r##'a '# b'##", &Config::default(), true).unwrap();
+    assert_eq!(format_buffer, "r##'a '# b'##");
+}
+
+#[test]
+fn nuon_rejects_code() {
+    let err = format_nuon("[1 2] | length", &Config::default(), false).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::NotData { found, span } if found == "|" && span == Span::new(6, 7)
+    ));
+
+    let err = format_nuon("ls", &Config::default(), false).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::NotData { found, span } if found == "ls" && span == Span::new(0, 2)
+    ));
+
+    let err = format_nuon("ls | get name", &Config::default(), false).unwrap_err();
+    assert!(matches!(
+        err,
+        FormatError::NotData { found, span } if found == "ls" && span == Span::new(0, 2)
+    ));
+
+    let err = format_nuon("[1 2]\n[3]", &Config::default(), false).unwrap_err();
+    assert!(matches!(err, FormatError::NotData { span, .. } if span == Span::new(6, 7)));
+
+    let err = format_nuon("{a: $x, b: \"|\"}", &Config::default(), false).unwrap_err();
+    assert!(matches!(err, FormatError::NotData { found, .. } if found == "$x"));
+}
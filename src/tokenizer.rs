@@ -1,4 +1,7 @@
-use crate::tokens::{Token, TokonizerTools};
+use crate::{
+    number::is_number,
+    tokens::{Token, TokonizerTools, GENERIC_TYPES, OPERATORS, REDIRECT_STREAMS, WORD_OPERATORS},
+};
 
 pub struct Tokonizer {
     pub index: usize,
@@ -84,6 +87,7 @@ impl Tokonizer {
         }
         false
     }
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_stack(&mut self, token: Token) {
         self.stack.push(token.clone())
     }
@@ -106,15 +110,13 @@ impl Tokonizer {
                     _ => {}
                 }
 
-                if !first && self.one_of_is_eq(token) {
-                    if depth == 0 {
-                        if included {
-                            block.push_str(&t.as_string());
-                        } else {
-                            self.prev();
-                        }
-                        break;
+                if !first && self.one_of_is_eq(token) && depth == 0 {
+                    if included {
+                        block.push_str(&t.as_string());
+                    } else {
+                        self.prev();
                     }
+                    break;
                 }
 
                 block.push_str(&t.as_string());
@@ -143,13 +145,408 @@ impl Tokonizer {
         block
     }
 
+    // the longest operator starting at the current token, with or without whitespace around it
+    // `$a==1`. Operators that also show up in words `*.txt`, `-f`, `/home`, `str-join` are only
+    // taken when they stand on their own or sit between two values `$a*2`, `1+2`. A number before
+    // a `-` or `/` is a date `2024-01-01` or a path `2024/01` instead
+    pub fn operator_at(&self) -> Option<&'static str> {
+        let rest = self.tokens[self.index..]
+            .iter()
+            .take(3)
+            .map(|e| e.as_string())
+            .collect::<String>();
+
+        let op = OPERATORS
+            .iter()
+            .filter(|op| rest.starts_with(**op))
+            .max_by_key(|op| op.len())?;
+        if self.is_type_parameter(op) {
+            return None;
+        }
+        if !op.starts_with(['+', '-', '*', '/']) {
+            return Some(op);
+        }
+
+        let after = self.tokens.get(self.index + op.len());
+        let standalone = self.temp.is_empty()
+            && matches!(
+                after,
+                Some(Token::WhiteSpace) | Some(Token::Tab(_)) | Some(Token::NewLine) | None
+            );
+
+        let value_before = match self.temp.is_empty() {
+            true => matches!(
+                self.stack.last(),
+                Some(Token::Number(_))
+                    | Some(Token::Variable(_))
+                    | Some(Token::CellPath(_))
+                    | Some(Token::ParenClose)
+                    | Some(Token::BraceSquareClosed)
+                    | Some(Token::DoubleQuoteBlock(_))
+                    | Some(Token::SingleQuoteBlock(_))
+            ),
+            false => is_number(&self.temp) && !op.starts_with(['-', '/']),
+        };
+        let value_after = matches!(after, Some(Token::Char(c)) if c.is_ascii_digit())
+            || matches!(
+                after,
+                Some(Token::Dolar)
+                    | Some(Token::ParenOpen)
+                    | Some(Token::BraceSquareOpen)
+                    | Some(Token::DoubleQuote)
+                    | Some(Token::SingleQuote)
+            );
+
+        match standalone || (value_before && value_after) {
+            true => Some(op),
+            false => None,
+        }
+    }
+
+    // the `<` and `>` around the type of a parameter `x: list<int>`, `record<a: list<int>>` or
+    // of a return type `nothing -> list<string>`
+    fn is_type_parameter(&self, op: &str) -> bool {
+        let line = self
+            .stack
+            .iter()
+            .rev()
+            .take_while(|e| **e != Token::NewLine)
+            .collect::<Vec<_>>();
+        let open = line.iter().filter(|e| ***e == Token::LessThen).count();
+        let closed = line.iter().filter(|e| ***e == Token::MoreThen).count();
+
+        match op {
+            "<" => {
+                GENERIC_TYPES.contains(&self.temp.as_str())
+                    || (!self.temp.is_empty()
+                        && (open > closed
+                            || line.iter().find(|e| ***e != Token::WhiteSpace)
+                                == Some(&&Token::Colon)))
+            }
+            ">" => open > closed,
+            _ => false,
+        }
+    }
+
+    // `..` starts a range when it has a bound `1..`, `..5`, `($a)..`. `../` is a path and
+    // `...$rest` a spread
+    pub fn is_range_start(&self) -> bool {
+        let dot = |offset: usize| self.tokens.get(self.index + offset) == Some(&Token::Char('.'));
+        if !dot(0) || !dot(1) || dot(2) {
+            return false;
+        }
+        if !self.temp.is_empty()
+            || matches!(
+                self.stack.last(),
+                Some(Token::ParenClose) | Some(Token::Variable(_))
+            )
+        {
+            return true;
+        }
+        matches!(
+            self.tokens.get(self.index + 2),
+            Some(Token::Char(c)) if c.is_ascii_digit()
+        ) || matches!(
+            self.tokens.get(self.index + 2),
+            Some(Token::Dolar) | Some(Token::ParenOpen) | Some(Token::Dash) | Some(Token::LessThen)
+        )
+    }
+    // takes a range starting at `..`, the start bound is taken back from the temp or the stack:
+    // `$a..$b`, `($a)..<($b)`, `1..2..10`
+    pub fn take_range(&mut self) -> String {
+        let mut start = std::mem::take(&mut self.temp);
+        if let (true, Some(Token::Variable(v))) = (start.is_empty(), self.stack.last()) {
+            start = v.clone();
+            self.stack.pop();
+        } else if start.is_empty() && self.stack.last() == Some(&Token::ParenClose) {
+            let mut depth = 0;
+            while let Some(t) = self.stack.pop() {
+                match t {
+                    Token::ParenClose => depth += 1,
+                    Token::ParenOpen => depth -= 1,
+                    _ => (),
+                }
+                start.insert_str(0, &t.as_string());
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+
+        let mut range = start;
+        let mut depth = 0;
+        while let Some(t) = self.get() {
+            match t {
+                Token::ParenOpen => depth += 1,
+                Token::ParenClose if depth > 0 => depth -= 1,
+                Token::WhiteSpace | Token::Tab(_) | Token::NewLine if depth > 0 => (),
+                Token::WhiteSpace
+                | Token::Tab(_)
+                | Token::NewLine
+                | Token::Comma
+                | Token::Pipe
+                | Token::Semicolon
+                | Token::ParenClose
+                | Token::BraceOpen
+                | Token::BraceClose
+                | Token::BraceSquareOpen
+                | Token::BraceSquareClosed => break,
+                _ => (),
+            }
+            range.push_str(&t.as_string());
+            self.next();
+        }
+        self.prev();
+        range
+    }
+
+    pub fn next_is_name_start(&self) -> bool {
+        matches!(self.peak_next(), Some(Token::Char(c)) if c.is_alphanumeric() || c == '_')
+    }
+    // the name of a variable and its cell path `env.config?.0."key"!`, or the cell path following
+    // a subexpression `.field`. The index is left on the last token of the path
+    pub fn take_cell_path(&mut self) -> String {
+        let mut path = String::new();
+
+        while let Some(t) = self.get() {
+            match t {
+                // `$a..$b` is a range
+                Token::Char('.') if self.next_eq(Token::Char('.')) => break,
+                Token::DoubleQuote | Token::SingleQuote if path.ends_with('.') => {
+                    path.push_str(&self.take_upto(std::slice::from_ref(&t), true));
+                }
+                Token::Char(c) if c.is_alphanumeric() || c == '_' || c == '.' => path.push(c),
+                // the optional `?` and required `!` modifiers, not the operators `$a!=1`, `$a!~"x"`
+                Token::Char('?') | Token::Exc
+                    if !path.is_empty()
+                        && !matches!(self.peak_next(), Some(Token::Equals) | Some(Token::Tilda)) =>
+                {
+                    path.push_str(&t.as_string())
+                }
+                Token::Dash if !path.is_empty() && !path.ends_with('.') => path.push('-'),
+                _ => break,
+            }
+            self.next();
+        }
+        self.prev();
+        path
+    }
+
+    // takes a path up to the whitespace or delimiter ending it, the index is left on the last
+    // token of the path. A comma only ends the paths of NUON data `{ a: /tmp, b: 1 }`
+    pub fn take_path(&mut self, comma: bool) -> String {
+        let mut path = String::new();
+
+        while let Some(t) = self.get() {
+            match t {
+                Token::WhiteSpace
+                | Token::Tab(_)
+                | Token::NewLine
+                | Token::Pipe
+                | Token::Semicolon
+                | Token::BraceClose
+                | Token::BraceSquareClosed
+                | Token::ParenClose => break,
+                Token::Comma if comma => break,
+                _ => path.push_str(&t.as_string()),
+            }
+            self.next();
+        }
+        self.prev();
+        path
+    }
+
+    // the `#` after the `r` of a raw string `r#'it's'#`
+    pub fn is_raw_string_start(&self) -> bool {
+        let mut rest = self.tokens[self.index..]
+            .iter()
+            .skip_while(|e| **e == Token::Hash);
+        self.temp == "r" && self.is_eq(&Token::Hash) && rest.next() == Some(&Token::SingleQuote)
+    }
+
+    // takes a raw string from the first `#` up to the quote and as many `#` closing it, the
+    // index is left on the last token of the string
+    pub fn take_raw_string(&mut self) -> String {
+        let hashes = self.tokens[self.index..]
+            .iter()
+            .take_while(|e| **e == Token::Hash)
+            .count();
+        let end = format!("'{}", "#".repeat(hashes));
+        let mut block = String::new();
+
+        while let Some(t) = self.get() {
+            block.push_str(&t.as_string());
+            if block.len() > hashes + 1 && block.ends_with(&end) {
+                break;
+            }
+            self.next();
+        }
+        block
+    }
+
+    // a colon inside the time of a datetime `2024-01-01T10:00`, not the one after a record key
+    pub fn is_datetime_colon(&self) -> bool {
+        self.temp.starts_with(|c: char| c.is_ascii_digit())
+            && self.temp.contains('T')
+            && matches!(self.peak_next(), Some(Token::Char(c)) if c.is_ascii_digit())
+    }
+
+    // a `>` directly after a stream name `o`, `err`, `o+e` is a redirection
+    pub fn is_redirect(&self) -> bool {
+        self.is_eq(&Token::MoreThen) && REDIRECT_STREAMS.contains(&self.temp.as_str())
+    }
+    // takes the redirection `o>`, `e>>` or stderr pipe `e>|` ending at the current token
+    pub fn take_redirect(&mut self) -> String {
+        let mut redirect = std::mem::take(&mut self.temp);
+        redirect.push('>');
+
+        if self.next_eq(Token::MoreThen) {
+            self.next();
+            redirect.push('>');
+        } else if self.next_eq(Token::Pipe) {
+            self.next();
+            redirect.push('|');
+        }
+        redirect
+    }
+
+    // `FOO=bar cmd` at the start of a statement sets an environment variable for the command,
+    // `(a=b)` without a command following it is left alone
+    pub fn is_env_shorthand(&self) -> bool {
+        let name = self.temp.chars().enumerate().all(|(i, c)| {
+            c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+        });
+        let statement_start = matches!(
+            self.stack.iter().rev().find(|e| **e != Token::WhiteSpace),
+            None | Some(Token::NewLine)
+                | Some(Token::Pipe)
+                | Some(Token::Semicolon)
+                | Some(Token::BraceOpen)
+                | Some(Token::ParenOpen)
+                | Some(Token::EnvShorthand(_))
+        );
+        if self.temp.is_empty() || !name || !statement_start || !self.is_eq(&Token::Equals) {
+            return false;
+        }
+
+        let mut index = self.index + 1;
+        let mut quote = None;
+        while let Some(token) = self.tokens.get(index) {
+            match (token, &quote) {
+                (Token::DoubleQuote | Token::SingleQuote, None) => quote = Some(token),
+                (t, Some(q)) if t == *q => quote = None,
+                (Token::WhiteSpace | Token::Tab(_), None) => break,
+                (Token::NewLine | Token::ParenClose | Token::BraceClose | Token::Pipe, None) => {
+                    return false
+                }
+                _ => (),
+            }
+            index += 1;
+        }
+
+        let command = self.tokens[index..]
+            .iter()
+            .find(|e| !matches!(e, Token::WhiteSpace | Token::Tab(_)));
+        !matches!(
+            command,
+            None | Some(Token::NewLine)
+                | Some(Token::Pipe)
+                | Some(Token::ParenClose)
+                | Some(Token::BraceClose)
+                | Some(Token::BraceSquareClosed)
+        )
+    }
+    // takes `FOO=bar` starting at the `=`, the name is in the temp
+    pub fn take_env_shorthand(&mut self) -> String {
+        let mut env = std::mem::take(&mut self.temp);
+
+        while let Some(t) = self.get() {
+            match t {
+                Token::DoubleQuote | Token::SingleQuote => {
+                    env.push_str(&self.take_upto(std::slice::from_ref(&t), true));
+                }
+                Token::WhiteSpace | Token::Tab(_) => break,
+                _ => env.push_str(&t.as_string()),
+            }
+            self.next();
+        }
+        self.prev();
+        env
+    }
+
+    // a dash at the start of a word followed by a letter starts a flag: `-l`, `--long`
+    pub fn is_flag_start(&self) -> bool {
+        if !self.temp.is_empty() || !self.is_eq(&Token::Dash) {
+            return false;
+        }
+        let mut index = self.index + 1;
+        if self.tokens.get(index) == Some(&Token::Dash) {
+            index += 1;
+        }
+        matches!(self.tokens.get(index), Some(Token::Char(c)) if c.is_ascii_alphabetic())
+    }
+    // takes a whole flag including its value `--name=value`, type `--name:int` or short alias
+    // `--name(-n)`, the index is left on the last token of the flag
+    pub fn take_flag(&mut self) -> String {
+        let mut flag = String::new();
+        let mut depth = 0;
+
+        while let Some(t) = self.get() {
+            match t {
+                Token::DoubleQuote | Token::SingleQuote => {
+                    flag.push_str(&self.take_upto(std::slice::from_ref(&t), true));
+                }
+                Token::ParenOpen => {
+                    depth += 1;
+                    flag.push_str(&t.as_string());
+                }
+                Token::ParenClose if depth > 0 => {
+                    depth -= 1;
+                    flag.push_str(&t.as_string());
+                }
+                Token::WhiteSpace | Token::Tab(_) | Token::NewLine if depth > 0 => {
+                    flag.push_str(&t.as_string());
+                }
+                Token::WhiteSpace
+                | Token::Tab(_)
+                | Token::NewLine
+                | Token::Pipe
+                | Token::Semicolon
+                | Token::Comma
+                | Token::ParenClose
+                | Token::BraceOpen
+                | Token::BraceClose
+                | Token::BraceSquareOpen
+                | Token::BraceSquareClosed => break,
+                // `--name: type` in a signature, the colon is spaced like any other
+                Token::Colon if matches!(self.peak_next(), Some(Token::WhiteSpace) | None) => {
+                    break
+                }
+                _ => flag.push_str(&t.as_string()),
+            }
+            self.next();
+        }
+        self.prev();
+        flag
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_temp(&mut self, s: String) {
         self.temp.push_str(&s);
     }
     pub fn temp_to_word_or_number(&mut self) {
         if !self.temp.is_empty() {
-            if self.temp.parse::<usize>().ok().is_some() {
+            // `$in` and the record key `in: 1` are not operators
+            let word_operator = WORD_OPERATORS.contains(&self.temp.as_str())
+                && self.stack.last() != Some(&Token::Dolar)
+                && !self.is_eq(&Token::Colon);
+
+            if is_number(&self.temp) {
                 self.to_stack(Token::Number(self.temp.clone()));
+            } else if self.temp.starts_with('^') {
+                self.to_stack(Token::External(self.temp.clone()));
+            } else if word_operator {
+                self.to_stack(Token::Operator(self.temp.clone()));
             } else {
                 self.to_stack(Token::Word(self.temp.clone()));
             }
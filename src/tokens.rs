@@ -1,6 +1,24 @@
 
 const TAB_MULTIPLIER: usize = 2;
 
+// binary operators made of symbols, lexed as one token when they stand on their own
+pub const OPERATORS: &[&str] = &[
+    "==", "!=", "<", "<=", ">", ">=", "=~", "!~", "+", "-", "*", "/", "//", "**", "++", "=", "+=",
+    "-=", "*=", "/=", "++=",
+];
+
+// streams that can be redirected `o>`, `e>>`, `o+e>|`
+pub const REDIRECT_STREAMS: &[&str] = &["o", "e", "o+e", "e+o", "out", "err", "out+err", "err+out"];
+
+// types taking the types they hold between `<` and `>` `list<int>`
+pub const GENERIC_TYPES: &[&str] = &["list", "record", "table", "oneof"];
+
+// binary operators spelled as words
+pub const WORD_OPERATORS: &[&str] = &[
+    "and", "or", "xor", "mod", "in", "not-in", "has", "not-has", "like", "not-like",
+    "starts-with", "ends-with", "bit-or", "bit-xor", "bit-and", "bit-shl", "bit-shr",
+];
+
 impl TokonizerTools for (usize, bool) {
     fn to_option(self) -> Option<usize> {
         let (i, b) = self;
@@ -20,6 +38,13 @@ pub enum Token {
     Char(char),
     Word(String),
     Number(String),
+    Range(String),
+    Variable(String),
+    CellPath(String),
+    Redirect(String),
+    External(String),
+    EnvShorthand(String),
+    FatArrow,
     Tilda,
     Att,
     BraceOpen,
@@ -39,9 +64,11 @@ pub enum Token {
     Pipe,
     Hash,
     Colon,
+    Semicolon,
     CommentBlock(String),
     Path(String),
     Dash,
+    Operator(String),
     Tag(String),
     TagLong(String),
     AttSomething(String),
@@ -50,6 +77,8 @@ pub enum Token {
     SingleQuote,
     SingleQuoteBlock(String),
     DoubleQuoteBlock(String),
+    // the original text of a region formatting is turned off for
+    Verbatim(String),
     Tab(usize),
 }
 
@@ -77,6 +106,7 @@ impl From<char> for Token {
             '<' => Self::LessThen,
             '#' => Self::Hash,
             ':' => Self::Colon,
+            ';' => Self::Semicolon,
             '@' => Self::Att,
             '~' => Self::Tilda,
             '-' => Self::Dash,
@@ -86,11 +116,27 @@ impl From<char> for Token {
 }
 
 impl Token {
+    // whitespace and separators the formatter adds and drops, every other token keeps its place
+    // relative to the others through all the passes except the sorting of imports
+    pub fn is_layout(&self) -> bool {
+        matches!(
+            self,
+            Token::WhiteSpace | Token::Tab(_) | Token::NewLine | Token::Comma
+        )
+    }
+
     pub fn as_string(&self) -> String {
         match self {
             Token::Char(c) => c.to_string(),
             Token::Word(w) => w.to_string(),
             Token::Number(w) => w.to_string(),
+            Token::Range(w) => w.to_string(),
+            Token::Variable(w) => w.to_string(),
+            Token::CellPath(w) => w.to_string(),
+            Token::Redirect(w) => w.to_string(),
+            Token::External(w) => w.to_string(),
+            Token::EnvShorthand(w) => w.to_string(),
+            Token::FatArrow => "=>".to_string(),
             Token::BraceOpen => "{".to_string(),
             Token::BraceClose => "}".to_string(),
             Token::WhiteSpace => " ".to_string(),
@@ -111,9 +157,11 @@ impl Token {
             Token::LessThen=> "<".to_string(),
             Token::Exc=> "!".to_string(),
             Token::Colon=> ":".to_string(),
+            Token::Semicolon => ";".to_string(),
             Token::Tilda=> "~".to_string(),
             Token::Att => "@".to_string(),
             Token::Dash => "-".to_string(),
+            Token::Operator(s) => s.to_string(),
             Token::Tag(s) => s.to_string(),
             Token::TagLong(s) => s.to_string(),
             Token::AttSomething(s) => s.to_string(),
@@ -121,6 +169,7 @@ impl Token {
             Token::Path(s) => s.to_string(),
             Token::DoubleQuoteBlock(s) => s.to_string(),
             Token::SingleQuoteBlock(s) => s.to_string(),
+            Token::Verbatim(s) => s.to_string(),
             Token::Tab(n) => vec!["\t"; *n * TAB_MULTIPLIER].join(""),
         }
     }
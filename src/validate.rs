@@ -0,0 +1,112 @@
+use crate::error::{FormatError, Span};
+
+fn closer(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+// `#` only starts a comment at the start of a word, `http://a#b` is a word
+fn starts_comment(prev: Option<char>) -> bool {
+    prev.is_none_or(|e| e.is_whitespace() || "([{;|,".contains(e))
+}
+
+// number of `#` of a raw string `r##'...'##`, `rest` is what follows the `r`
+fn raw_string_hashes(rest: &str) -> Option<usize> {
+    let hashes = rest.len() - rest.trim_start_matches('#').len();
+    (hashes > 0 && rest[hashes..].starts_with('\'')).then_some(hashes)
+}
+
+// Where the delimiters and strings of a valid source are
+#[derive(Debug, Default)]
+pub struct Structure {
+    // byte offset of every opening delimiter and the one closing it
+    pub pairs: Vec<(usize, usize)>,
+    pub strings: Vec<Span>,
+}
+
+// Checks the source is something the formatter can safely lay out: every string is closed,
+// the delimiters are balanced, there are no stray control characters and formatting isn't
+// turned off for half a block
+pub fn validate(buffer: &str) -> Result<(), FormatError> {
+    crate::directives::check(buffer, &structure(buffer)?)
+}
+
+pub fn structure(buffer: &str) -> Result<Structure, FormatError> {
+    let mut chars = buffer.char_indices().peekable();
+    let mut delimiters: Vec<(char, Span)> = Vec::new();
+    let mut structure = Structure::default();
+    let mut prev = None;
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '#' if starts_comment(prev) => while chars.next_if(|(_, e)| *e != '\n').is_some() {},
+            // raw string `r#'it's'#`
+            'r' if !prev.is_some_and(|e: char| e.is_alphanumeric())
+                && raw_string_hashes(&buffer[index + 1..]).is_some() =>
+            {
+                let hashes = raw_string_hashes(&buffer[index + 1..]).unwrap_or_default();
+                let body = index + 2 + hashes;
+                let end = format!("'{}", "#".repeat(hashes));
+                let Some(close) = buffer[body..].find(&end) else {
+                    return Err(FormatError::UnterminatedString {
+                        quote: '\'',
+                        span: Span::new(index, body),
+                    });
+                };
+                let after = body + close + end.len();
+                while chars.next_if(|(i, _)| *i < after).is_some() {}
+                structure.strings.push(Span::new(index, after));
+            }
+            '"' | '\'' | '`' => {
+                let mut escaped = false;
+                let close = chars.find(|(_, e)| {
+                    let end = *e == c && !escaped;
+                    // only double quoted strings have escapes
+                    escaped = c == '"' && *e == '\\' && !escaped;
+                    end
+                });
+                let Some((close, _)) = close else {
+                    return Err(FormatError::UnterminatedString {
+                        quote: c,
+                        span: Span::new(index, index + 1),
+                    });
+                };
+                structure.strings.push(Span::new(index, close + 1));
+            }
+            '(' | '[' | '{' => delimiters.push((c, Span::new(index, index + 1))),
+            ')' | ']' | '}' => {
+                let close = (c, Span::new(index, index + 1));
+                match delimiters.pop() {
+                    Some((open, span)) if closer(open) == c => {
+                        structure.pairs.push((span.start, index))
+                    }
+                    open => {
+                        return Err(FormatError::UnbalancedDelimiter {
+                            open,
+                            close: Some(close),
+                        })
+                    }
+                }
+            }
+            _ if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {
+                return Err(FormatError::InvalidToken {
+                    token: c,
+                    span: Span::new(index, index + c.len_utf8()),
+                });
+            }
+            _ => (),
+        }
+        prev = Some(c);
+    }
+
+    match delimiters.pop() {
+        Some(open) => Err(FormatError::UnbalancedDelimiter {
+            open: Some(open),
+            close: None,
+        }),
+        None => Ok(structure),
+    }
+}